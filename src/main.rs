@@ -1,12 +1,26 @@
 mod slug;
 
 use std::rc::Rc;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
 
 use slug::syntax;
+use syntax::Loader;
 use syntax::lexer::{BlockTree, process_branch};
-use syntax::parser::{Traveler, Parser, Expression};
+use syntax::parser::{Traveler, Parser, Expression, ASYNC_RUNTIME, async_used};
 
 fn main() {
+    // slug main.slug, compiles it and its imports to lua files next to them
+    if let Some(file) = env::args().nth(1) {
+        if !compile(Path::new(&file)) {
+            process::exit(1)
+        }
+
+        return
+    }
+
     let test = r#"
 
     "#;
@@ -23,24 +37,76 @@ fn main() {
     let symtab  = Rc::new(syntax::SymTab::new_global());
     let typetab = Rc::new(syntax::TypeTab::new_global());
 
-    match parser.parse() {
-        Err(why)  => println!("error: {}", why),
-        Ok(stuff) => {
-            println!("{:#?}", stuff);
-            
-            for s in stuff.iter() {
-                match s.visit(&symtab, &typetab) {
-                    Ok(()) => (),
-                    Err(e) => {
-                        println!("{}", e);
-                        return
-                    },
-                }
-            }
-            
-            println!("{}", Expression::Block(Rc::new(stuff)))
-        },
+    let (stuff, diagnostics) = parser.parse();
+
+    for why in diagnostics.iter() {
+        println!("error: {}", why)
+    }
+
+    println!("{:#?}", stuff);
+
+    // the partial tree is still checked, for every error at once
+    let mut failed = !diagnostics.is_empty();
+
+    for s in stuff.iter() {
+        if let Err(e) = s.visit(&symtab, &typetab) {
+            println!("{}", e);
+            failed = true
+        }
+    }
+
+    if failed {
+        return
+    }
+
+    for w in symtab.warnings() {
+        println!("warning: {}", w)
+    }
+
+    let lua = format!("{}", Expression::Block(Rc::new(stuff)));
+
+    // the async runtime goes first, once
+    if async_used() {
+        println!("local __async = (function()\n{}end)()", ASYNC_RUNTIME)
     }
+
+    println!("{}", lua);
         
     println!("{:?}\n{:?}", symtab, typetab);
 }
+
+// false if anything went wrong, after saying what
+fn compile(file: &Path) -> bool {
+    let root = file.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+    let name = match file.file_stem() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None       => {
+            println!("error: {}: not a file", file.display());
+            return false
+        },
+    };
+
+    let loader = Rc::new(Loader::new(root));
+
+    if let Err(e) = loader.load(&name) {
+        println!("error: {}", e);
+        return false
+    }
+
+    for w in loader.warnings() {
+        println!("warning: {}", w)
+    }
+
+    let mut written = true;
+
+    for (module, lua) in loader.output() {
+        let path = loader.path(&module, "lua");
+
+        if let Err(e) = fs::write(&path, lua) {
+            println!("error: can't write '{}': {}", path.display(), e);
+            written = false
+        }
+    }
+
+    written
+}
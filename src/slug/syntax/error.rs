@@ -19,13 +19,6 @@ impl RunError {
             position: None,
         }
     }
-
-    pub fn new_pos(position: TokenPosition, value: &str) -> RunError {
-        RunError {
-            value: RunErrorValue::Constant(value.to_owned()),
-            position: Some(position),
-        }
-    }
 }
 
 impl fmt::Display for RunError {
@@ -1,8 +1,8 @@
-use super::Token;
+use super::{Token, TokenPosition};
 
 #[derive(Debug)]
 pub enum ChunkValue {
-    Source(String),
+    Source(String, TokenPosition), // a line, and where it starts in the file
     Tokens(Vec<Token>),
     Block(Branch),
 }
@@ -43,36 +43,53 @@ pub struct BlockTree<'a> {
     current_line: usize,
     inside: i32,
     inside_brace: i32,
+    open_indent: usize, // of the line the open brackets are on
 }
 
 #[allow(dead_code)]
 impl<'a> BlockTree<'a> {
-    pub fn new(source: &str, current_line: usize) -> BlockTree {
+    pub fn new(source: &'a str, current_line: usize) -> BlockTree<'a> {
         BlockTree {
             source,
             current_line,
             inside: 0,
             inside_brace: 0,
+            open_indent: 0,
         }
     }
 
-    pub fn indents(&mut self) -> Vec<(usize, &'a str)> {
+    pub fn indents(&mut self) -> Vec<(usize, &'a str, TokenPosition)> {
         let mut indents = Vec::new();
-        let mut lines   = self.source.lines();
-        while let Some(line) = lines.next() {
+        for (i, line) in self.source.lines().enumerate() {
             let parts: Vec<&str> = line.split("~").collect();
-            let ln = parts.get(0).unwrap().trim();
+            let ln = parts.first().unwrap().trim();
 
-            if ln.len() > 0 {
-                let indent = self.indent(&line);
-                indents.push((indent, ln))
+            if !ln.is_empty() {
+                let start  = TokenPosition::new(i + 1, line.len() - line.trim_start().len());
+                let indent = self.indent(line);
+                indents.push((indent, ln, start))
             }
         }
         indents
     }
 
     pub fn indent(&mut self, line: &str) -> usize {
-        let mut pos: usize = 0;
+        let pos = line.chars().take_while(|&c| c == ' ' || c == '\t').count();
+
+        // a bracket open at a line no deeper than where it was opened is never closed
+        if (self.inside > 0 || self.inside_brace > 0) && pos <= self.open_indent && !line.trim_start().starts_with([')', ']']) {
+            self.inside       = 0;
+            self.inside_brace = 0;
+        }
+
+        // lines inside brackets go with the line that opened them
+        let pos = if self.inside > 0 || self.inside_brace > 0 {
+            self.open_indent
+        } else {
+            self.open_indent = pos;
+            pos
+        };
+
         for c in line.chars() {
             match c {
                 '[' => self.inside_brace += 1,
@@ -83,39 +100,26 @@ impl<'a> BlockTree<'a> {
             }
         }
 
-        for c in line.chars() {
-            match c {
-                ' ' | '\t' => {
-                    if self.inside > 0 || self.inside_brace > 0 {
-                        continue
-                    }
-
-                    pos += 1
-                }
-                _ => break,
-            }
-        }
-        
         pos
     }
 
-    pub fn tree(&mut self, indents: &Vec<(usize, &'a str)>) -> Branch {
+    pub fn tree(&mut self, indents: &Vec<(usize, &'a str, TokenPosition)>) -> Branch {
         let mut branch = Branch::new(Vec::new());
         let line       = indents.get(self.current_line);
-        let &(base_indent, _) = match line {
+        let &(base_indent, _, _) = match line {
             Some(i) => i,
             None    => return branch,
         };
 
         while self.current_line < indents.len() {
-            let (indent, line) = indents[self.current_line];
+            let (indent, line, start) = indents[self.current_line];
             if indent == base_indent {
-                branch.value.push(Chunk::new(ChunkValue::Source(line.to_owned())))
+                branch.value.push(Chunk::new(ChunkValue::Source(line.to_owned(), start)))
             } else if indent < base_indent {
                 self.current_line -= 1;
                 return branch
             } else if indent > base_indent {
-                branch.value.push(Chunk::new(ChunkValue::Block(self.tree(&indents))))
+                branch.value.push(Chunk::new(ChunkValue::Block(self.tree(indents))))
             }
             self.current_line += 1
         }
@@ -11,21 +11,37 @@ pub fn lexer(data: &mut Chars) -> Lexer {
     let mut lexer = Lexer::new(tokenizer);
 
     let symbols = vec![
+        "++=",
+        "+=",
+        "-=",
+        "*=",
+        "/=",
+        "%=",
+        "^=",
         "(",
         ")",
         "[",
         "]",
         ",",
+        "::",
         ":",
         "{",
         "}",
         "!",
+        "|>",
         "|",
         "=",
         "..",
         ".",
     ].iter().map(|&x| x.to_string()).collect();
 
+    let comparisons = [
+        "==",
+        "!=",
+        ">=",
+        "<=",
+    ].iter().map(|&x| x.to_string()).collect();
+
     let operators = vec![
         "++",
         "+",
@@ -41,26 +57,32 @@ pub fn lexer(data: &mut Chars) -> Lexer {
         "<=",
         "==",
         "!=",
+    ].iter().map(|&x| x.to_string()).collect();
+
+    // only whole words, "orc" is a name
+    let word_operators = [
         "and",
         "or",
     ].iter().map(|&x| x.to_string()).collect();
 
     let keywords = vec![
-        "if", "else", "elif", "unless", "return", "fun",
+        "if", "else", "elif", "unless", "return", "fun", "match", "record", "enum", "interface", "nil", "for", "in", "import", "export", "class", "super", "try", "catch", "finally", "throw", "async", "await", "yield",
     ].iter().map(|&x| x.to_string()).collect();
 
-    let types = vec![
+    let types = [
         "num", "str", "any", "bool",
     ].iter().map(|&x| x.to_string()).collect();
 
-    let boolean = vec![
+    let boolean = [
         "true",
         "false",
     ].iter().map(|&x| x.to_string()).collect();
 
     let matcher_types          = KeyMatcher::new(TokenType::Type, types);
+    let matcher_comparison     = ConstantMatcher::new(TokenType::Operator, comparisons);
     let matcher_symbol         = ConstantMatcher::new(TokenType::Symbol, symbols);
     let matcher_operator       = ConstantMatcher::new(TokenType::Operator, operators);
+    let matcher_word_operator  = KeyMatcher::new(TokenType::Operator, word_operators);
     let matcher_boolean        = KeyMatcher::new(TokenType::BoolLiteral, boolean);
     let matcher_keyword        = KeyMatcher::new(TokenType::Keyword, keywords);
     let matcher_whitespace     = WhitespaceMatcher {};
@@ -70,12 +92,14 @@ pub fn lexer(data: &mut Chars) -> Lexer {
     let matcher_string_literal = StringLiteralMatcher {};
 
     lexer.matchers_mut().push(Rc::new(matcher_whitespace));
+    lexer.matchers_mut().push(Rc::new(matcher_comparison));
     lexer.matchers_mut().push(Rc::new(matcher_symbol));
     lexer.matchers_mut().push(Rc::new(matcher_float_literal));
     lexer.matchers_mut().push(Rc::new(matcher_int_literal));
     lexer.matchers_mut().push(Rc::new(matcher_string_literal));
     lexer.matchers_mut().push(Rc::new(matcher_types));
     lexer.matchers_mut().push(Rc::new(matcher_operator));
+    lexer.matchers_mut().push(Rc::new(matcher_word_operator));
     lexer.matchers_mut().push(Rc::new(matcher_boolean));
     lexer.matchers_mut().push(Rc::new(matcher_keyword));
     lexer.matchers_mut().push(Rc::new(matcher_identifier));
@@ -86,15 +110,21 @@ pub fn lex_branch(branch: &Branch) -> Branch {
     let mut lexed_branch = Branch::new(Vec::new());
     for c in branch.value.iter() {
         match c.value() {
-            &ChunkValue::Source(ref s) => {
+            ChunkValue::Source(s, start) => {
                 let mut line: Vec<Token> = lexer(&mut s.clone().chars()).collect();
-                line.push(Token::new(TokenType::EOL, TokenPosition::default(), "\n".to_owned()));
+
+                // each line is lexed alone, from the start of the file
+                for token in line.iter_mut() {
+                    token.position = TokenPosition::new(start.line, start.col + token.position.col)
+                }
+
+                line.push(Token::new(TokenType::Eol, TokenPosition::new(start.line, start.col + s.len()), "\n".to_owned()));
 
                 let chunk = ChunkValue::Tokens(line);
                 lexed_branch.value.push(Chunk::new(chunk))
             },
-            &ChunkValue::Block(ref b) => {
-                let chunk = ChunkValue::Block(lex_branch(&b));
+            ChunkValue::Block(b) => {
+                let chunk = ChunkValue::Block(lex_branch(b));
                 lexed_branch.value.push(Chunk::new(chunk))
             },
             _ => (),
@@ -107,8 +137,8 @@ pub fn flatten_branch(branch: &Branch) -> Vec<Token> {
     let mut flat = Vec::new();
     for c in branch.value.iter() {
         match c.value() {
-            &ChunkValue::Tokens(ref t) => flat.append(&mut t.clone()),
-            &ChunkValue::Block(ref b)  => flat.push(Token::new(TokenType::Block(flatten_branch(b)), TokenPosition::new(0, 0), "".to_string())),
+            ChunkValue::Tokens(t) => flat.append(&mut t.clone()),
+            ChunkValue::Block(b) => flat.push(Token::new(TokenType::Block(flatten_branch(b)), TokenPosition::new(0, 0), "".to_string())),
             _ => continue,
         }
     }
@@ -121,7 +151,7 @@ pub fn process_branch(branch: &Branch) -> Vec<Token> {
 
 pub struct Lexer {
     tokenizer: Tokenizer,
-    matchers: Vec<Rc<Matcher>>,
+    matchers: Vec<Rc<dyn Matcher>>,
 }
 
 #[allow(dead_code)]
@@ -143,11 +173,11 @@ impl Lexer {
         None
     }
 
-    pub fn matchers(&self) -> &Vec<Rc<Matcher>> {
+    pub fn matchers(&self) -> &Vec<Rc<dyn Matcher>> {
         &self.matchers
     }
 
-    pub fn matchers_mut(&mut self) -> &mut Vec<Rc<Matcher>> {
+    pub fn matchers_mut(&mut self) -> &mut Vec<Rc<dyn Matcher>> {
         &mut self.matchers
     }
 }
@@ -158,12 +188,9 @@ impl Iterator for Lexer {
     fn next(&mut self) -> Option<Token> {
         let token = self.match_token().unwrap();
         match token.token_type {
-            TokenType::EOF => None,
+            TokenType::Eof => None,
             TokenType::Whitespace => {
-                match self.next() {
-                    Some(t) => Some(t),
-                    None => None,
-                }
+                self.next()
             }
             _ => Some(token),
         }
@@ -44,20 +44,20 @@ impl Matcher for IntLiteralMatcher {
             Some(&'+') => Some(true),
             _          => None,
         };
-        if let Some(_) = prefix {
+        if prefix.is_some() {
             tokenizer.advance(1)
         };
-        while !tokenizer.end() && tokenizer.peek().unwrap().is_digit(10) {
+        while !tokenizer.end() && tokenizer.peek().unwrap().is_ascii_digit() {
             accum.push(tokenizer.next().unwrap());
         }
         if !accum.is_empty() {
             let literal: String = if Some(false) == prefix {
-                match i64::from_str_radix(accum.as_str(), 10) {
+                match accum.parse::<i64>() {
                     Ok(result) => format!("-{}", result),
                     Err(error) => panic!("unable to parse int-literal: {}", error)
                 }
             } else {
-                match u64::from_str_radix(accum.as_str(), 10) {
+                match accum.parse::<u64>() {
                     Ok(result) => result.to_string(),
                     Err(error) => panic!("unable to parse int-literal: {}", error)
                 }
@@ -80,12 +80,12 @@ impl Matcher for FloatLiteralMatcher {
             Some(&'+') => Some(true),
             _          => None,
         };
-        if let Some(_) = prefix {
+        if prefix.is_some() {
             tokenizer.advance(1)
         };
 
-        let curr = tokenizer.next().unwrap();
-        if curr.is_digit(10) {
+        let curr = tokenizer.next()?;
+        if curr.is_ascii_digit() {
             accum.push(curr)
         } else if curr == '.' {
             accum.push_str("0.")
@@ -94,7 +94,7 @@ impl Matcher for FloatLiteralMatcher {
         }
         while !tokenizer.end() {
             let current = *tokenizer.peek().unwrap();
-            if !current.is_whitespace() && current.is_digit(10) || current == '.' {
+            if !current.is_whitespace() && current.is_ascii_digit() || current == '.' {
                 if current == '.' && accum.contains('.') {
                     panic!("illegal decimal point")
                 }
@@ -104,7 +104,7 @@ impl Matcher for FloatLiteralMatcher {
             }
         }
 
-        if accum == "0.".to_owned() {
+        if accum == "0." {
             None
         } else if accum.contains('.') {
 
@@ -123,12 +123,12 @@ impl Matcher for FloatLiteralMatcher {
             token!(tokenizer, FloatLiteral, literal)
         } else {
             let literal: String = if Some(false) == prefix {
-                match i64::from_str_radix(accum.as_str(), 10) {
+                match accum.parse::<i64>() {
                     Ok(result) => format!("-{}", result),
                     Err(error) => panic!("unable to parse int-literal: {}", error)
                 }
             } else {
-                match u64::from_str_radix(accum.as_str(), 10) {
+                match accum.parse::<u64>() {
                     Ok(result) => result.to_string(),
                     Err(error) => panic!("unable to parse int-literal: {}", error)
                 }
@@ -144,11 +144,11 @@ pub struct StringLiteralMatcher;
 impl Matcher for StringLiteralMatcher {
     fn try_match(&self, tokenizer: &mut Tokenizer) -> Option<Token> {
         let mut raw_marker = false;
-        let delimeter  = match tokenizer.peek().unwrap() {
-            &'"'  => Some('"'),
-            &'\'' => Some('\''),
-            &'r' => match *tokenizer.peek_n(1).unwrap() {
-                c @ '"' | c @ '\'' => {
+        let delimeter  = match *tokenizer.peek().unwrap() {
+            '"'  => Some('"'),
+            '\'' => Some('\''),
+            'r' => match tokenizer.peek_n(1).cloned() {
+                Some(c @ '"') | Some(c @ '\'') => {
                     raw_marker = true;
                     tokenizer.advance(1); // Skips prefix
                     Some(c)
@@ -183,12 +183,12 @@ impl Matcher for StringLiteralMatcher {
                     );
                     found_escape = false
                 } else {
-                    match tokenizer.peek().unwrap() {
-                        &'\\' => {
+                    match *tokenizer.peek().unwrap() {
+                        '\\' => {
                             tokenizer.next();
                             found_escape = true
                         },
-                        &c if &c == &delimeter => break,
+                        c if c == delimeter => break,
                         _ => string.push(tokenizer.next().unwrap()),
                     }
                 }
@@ -207,8 +207,8 @@ pub struct ConstantMatcher {
 impl ConstantMatcher {
     pub fn new(token_type: TokenType, constants: Vec<String>) -> Self {
         ConstantMatcher {
-            token_type: token_type,
-            constants: constants,
+            token_type,
+            constants,
         }
     }
 }
@@ -248,17 +248,13 @@ impl Matcher for KeyMatcher {
         for constant in self.constants.clone() {
             let dat = tokenizer.clone().take(constant.len());
             if dat.size_hint().1.unwrap() != constant.len() {
-                return None
+                continue
             }
             if dat.collect::<String>() == constant {
-                match tokenizer.peek_n(constant.len()) {
-                    Some(c) => {
-                        if "_@?'".contains(*c) || c.is_alphanumeric() {
-                            return None
-                        }
-                    },
-                    
-                    None => (),
+                if let Some(c) = tokenizer.peek_n(constant.len()) {
+                    if "_@?'".contains(*c) || c.is_alphanumeric() {
+                        continue
+                    }
                 }
 
                 tokenizer.advance(constant.len());
@@ -2,6 +2,7 @@ pub mod token;
 pub mod block_tree;
 pub mod tokenizer;
 pub mod matcher;
+#[allow(clippy::module_inception)]
 pub mod lexer;
 
 pub use self::token::*;
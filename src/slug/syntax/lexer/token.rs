@@ -13,8 +13,8 @@ pub enum TokenType {
     Keyword,
     Type,
     Whitespace,
-    EOL,
-    EOF,
+    Eol,
+    Eof,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -68,12 +68,8 @@ impl Token {
     }
 }
 
-impl<'a> PartialEq for Token {
+impl PartialEq for Token {
     fn eq(&self, other: &Token) -> bool {
         self.token_type == other.token_type
     }
-
-    fn ne(&self, other: &Token) -> bool {
-        self.token_type != other.token_type
-    }
 }
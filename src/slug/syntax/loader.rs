@@ -0,0 +1,174 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use super::{SymTab, TypeTab};
+use super::lexer::{BlockTree, process_branch};
+use super::parser::{Traveler, Parser, ParserResult, ParserError, Statement, Expression, Type, Fields, ASYNC_RUNTIME, async_used};
+
+// finds, checks and compiles the modules of a program
+pub struct Loader {
+    root:    PathBuf,
+    exports: RefCell<HashMap<String, Type>>, // checked modules by name
+    types:   RefCell<HashMap<String, Fields>>, // the types each module exports
+    chain:   RefCell<Vec<String>>,           // modules being checked, the importing ones first
+    output:  RefCell<Vec<(String, String)>>, // lua of each module, imported ones first
+    warnings: RefCell<Vec<String>>,
+}
+
+impl Loader {
+    pub fn new(root: PathBuf) -> Loader {
+        Loader {
+            root,
+            exports: RefCell::new(HashMap::new()),
+            types:   RefCell::new(HashMap::new()),
+            chain:   RefCell::new(Vec::new()),
+            output:  RefCell::new(Vec::new()),
+            warnings: RefCell::new(Vec::new()),
+        }
+    }
+
+    // ui.button is ui/button.slug
+    pub fn path(&self, name: &str, extension: &str) -> PathBuf {
+        let mut path = self.root.clone();
+
+        for part in name.split('.') {
+            path.push(part)
+        }
+
+        path.set_extension(extension);
+        path
+    }
+
+    // the exports of a module, checked the first time it's imported
+    pub fn load(self: &Rc<Self>, name: &str) -> ParserResult<Type> {
+        if let Some(t) = self.exports.borrow().get(name) {
+            return Ok(t.clone())
+        }
+
+        if self.chain.borrow().iter().any(|n| n == name) {
+            let mut chain = self.chain.borrow().clone();
+            chain.push(name.to_string());
+
+            return Err(ParserError::new(&format!("import cycle: {}", chain.join(" -> "))))
+        }
+
+        let path = self.path(name, "slug");
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e)     => return Err(ParserError::new(&format!("{}: can't read '{}': {}", name, path.display(), e))),
+        };
+
+        self.chain.borrow_mut().push(name.to_string());
+        let checked = self.check(name, &source);
+        self.chain.borrow_mut().pop();
+
+        let t = checked?;
+
+        self.exports.borrow_mut().insert(name.to_string(), t.clone());
+
+        Ok(t)
+    }
+
+    // the records, enums and classes a module exports, for annotations
+    pub fn load_types(self: &Rc<Self>, name: &str) -> ParserResult<Fields> {
+        self.load(name)?;
+
+        Ok(self.types.borrow().get(name).cloned().unwrap_or_default())
+    }
+
+    fn check(self: &Rc<Self>, name: &str, source: &str) -> ParserResult<Type> {
+        let mut blocks = BlockTree::new(source, 0);
+        let indents    = blocks.indents();
+
+        let root = blocks.tree(&indents);
+        let done = process_branch(&root);
+
+        let mut parser = Parser::new_module(Traveler::new(done), self.clone());
+
+        let (statements, mut errors) = parser.parse();
+
+        let mut types = Vec::new();
+
+        for s in statements.iter() {
+            if let Statement::Export(ref exported) = *s {
+                if let Some(n) = exported.type_name() {
+                    if let Some(t) = parser.declared_type(&n) {
+                        types.push((n, t))
+                    }
+                }
+            }
+        }
+
+        self.types.borrow_mut().insert(name.to_string(), Rc::new(types));
+
+        let sym = Rc::new(SymTab::new_module(self.clone()));
+        let env = Rc::new(TypeTab::new_global());
+
+        for s in statements.iter() {
+            if let Err(e) = s.visit(&sym, &env) {
+                errors.push(e)
+            }
+        }
+
+        // every error of the module at once
+        if !errors.is_empty() {
+            let errors: Vec<String> = errors.iter().map(|e| format!("{}: {}", name, e)).collect();
+            return Err(ParserError::new(&errors.join("\n")))
+        }
+
+        for w in sym.warnings() {
+            self.warnings.borrow_mut().push(format!("{}: {}", name, w))
+        }
+
+        // the module is a table of its exports
+        let mut fields = Vec::new();
+
+        for s in statements.iter() {
+            if let Statement::Export(ref exported) = *s {
+                for n in exported.exported_names() {
+                    let t = Expression::Identifier(n.clone()).get_type(&sym, &env)?;
+                    fields.push((n, t))
+                }
+            }
+        }
+
+        // funs and types stay local to the module, declared up front for uses before them
+        let locals: Vec<String> = statements.iter().filter_map(|s| s.type_name().or_else(|| s.fun_name())).map(|n| n.to_string()).collect();
+
+        let locals = if locals.is_empty() {
+            String::new()
+        } else {
+            format!("local {}\n", locals.join(", "))
+        };
+
+        let table: Vec<String> = fields.iter().map(|(n, _)| format!("{} = {}", n, n)).collect();
+        let mut lua = format!("{}{}return {{{}}}\n", locals, Expression::Block(Rc::new(statements)), table.join(", "));
+
+        // modules share one runtime, written next to them
+        if async_used() {
+            lua = format!("local __async = require(\"__async\")\n{}", lua);
+
+            let mut output = self.output.borrow_mut();
+
+            if !output.iter().any(|(n, _)| n == "__async") {
+                output.push(("__async".to_string(), ASYNC_RUNTIME.to_string()))
+            }
+        }
+
+        self.output.borrow_mut().push((name.to_string(), lua));
+
+        Ok(Type::Record(Rc::new(name.to_string()), Rc::new(fields), Rc::new(Vec::new())))
+    }
+
+    pub fn output(&self) -> Vec<(String, String)> {
+        self.output.borrow().clone()
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings.borrow().clone()
+    }
+}
@@ -1,10 +1,31 @@
  use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 
 use super::{ParserResult, ParserError};
+use super::runtime::use_async;
 use super::super::{SymTab, TypeTab};
 
 use std::fmt;
 
+thread_local!(static TEMP_COUNT: Cell<usize> = const { Cell::new(0) });
+
+// set while writing a try body, whose returns have to get out of its closure
+thread_local!(static IN_TRY: Cell<bool> = const { Cell::new(false) });
+
+// unique names for the locals that lowering needs to introduce
+pub fn temp_name(prefix: &str) -> String {
+    TEMP_COUNT.with(|c| {
+        let n = c.get();
+        c.set(n + 1);
+
+        format!("__{}{}", prefix, n)
+    })
+}
+
+// the names an assignment declares, set by visit, the others are already in scope
+pub type Declared = Rc<RefCell<Vec<Rc<String>>>>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Block(Rc<Vec<Statement>>),
@@ -12,21 +33,50 @@ pub enum Expression {
     StringLiteral(Rc<String>),
     Identifier(Rc<String>),
     BoolLiteral(bool),
+    NilLiteral,
     DictLiteral(Rc<Vec<Expression>>),
     Call(Rc<Expression>, Rc<Vec<Expression>>),
-    Index(Rc<Expression>, Rc<Expression>),
-    Definition(Option<Type>, Rc<Expression>, Option<Rc<Expression>>),
-    EOF,
+    MethodCall(Rc<Expression>, Rc<String>, Rc<Vec<Expression>>),
+    Pipe(Rc<Expression>, Rc<Expression>),
+    NamedCall {
+        id:    Rc<Expression>,
+        args:  Rc<Vec<Expression>>,
+        named: Rc<Vec<(Rc<String>, Expression)>>,
+        order: Rc<RefCell<Vec<Option<usize>>>>, // set by visit, which value goes to each param, args then named ones
+    },
+    Index(Rc<Expression>, Rc<Expression>, Rc<Cell<bool>>), // set by visit, when the key goes to the user's []
+    Definition(Option<Type>, Rc<Expression>, Option<Rc<Expression>>, Declared),
+    Compound(Rc<Expression>, Operand, Rc<Expression>),
+    MultiDefinition(Rc<Vec<Expression>>, Rc<Vec<Expression>>, Declared),
+    Eof,
     Operation {
         left:  Rc<Expression>,
         op:    Operand,
         right: Rc<Expression>,
     },
     Fun {
-        param_names: Rc<Vec<Rc<String>>>,
-        param_types: Rc<Vec<Type>>,
-        t:           Option<Type>,
-        body:        Rc<Vec<Statement>>,
+        param_names:    Rc<Vec<Rc<String>>>,
+        param_types:    Rc<Vec<Type>>,
+        param_defaults: Rc<Vec<Option<Rc<Expression>>>>,
+        t:              Option<Type>,
+        body:           Rc<Vec<Statement>>,
+    },
+    Destructure {
+        names: Rc<Vec<Rc<String>>>,
+        value: Rc<Expression>,
+        keys:  Option<Rc<Vec<Rc<String>>>>, // the field of each name, by position when none
+        keyed: Rc<Cell<bool>>, // set by visit, names without keys are fields of keyed values
+        declared: Declared,
+    },
+    Match(Rc<Expression>, Rc<Vec<Arm>>),
+    If(Rc<Vec<Branch>>, Option<Rc<Vec<Statement>>>), // if and elifs, then else
+    Throw(Rc<Expression>),
+    Await(Rc<Expression>),
+    Yield(Rc<Expression>),
+    Super {
+        parent: Rc<String>,
+        method: Option<Rc<String>>, // the constructor when none
+        args:   Rc<Vec<Expression>>,
     },
 }
 
@@ -37,27 +87,89 @@ impl Expression {
             Expression::NumberLiteral(_)  => Ok(Type::Num),
             Expression::StringLiteral(_)  => Ok(Type::Str),
             Expression::BoolLiteral(_)    => Ok(Type::Bool),
-            Expression::Identifier(ref n) => match sym.get_name(&*n) {
+            Expression::NilLiteral        => Ok(Type::Nil),
+            Expression::Identifier(ref n) => match sym.get_name(n) {
                 Some((i, env_index)) => {
                     Ok(env.get_type(i, env_index).unwrap())
                 },
                 None => Err(ParserError::new(&format!("unexpected use of: {}", n))),
             },
 
-            Expression::Index(ref a, ref b) => {
-                match a.get_type(&sym, &env)? {
+            Expression::Index(ref a, ref b, ref custom) => {
+                let t = a.get_type(sym, env)?.complete(sym);
+
+                // what isn't a field goes to the user's []
+                if let Some(Type::Fun(ref params, _)) = t.operator("__index") {
+                    let (field, key) = match **b {
+                        Expression::Identifier(ref field) => (t.field(field), Type::Str),
+                        ref e                             => (None, e.get_type(sym, env)?),
+                    };
+
+                    if field.is_none() && params.len() > 2 {
+                        custom.set(true);
+
+                        if !params[2].compare(&key) {
+                            return Err(ParserError::new(&format!("{}: expected key '{:?}', got '{:?}'", a, params[2], key)))
+                        }
+
+                        return Ok(params[0].clone())
+                    }
+                }
+
+                match t {
                     Type::Array(ref t) => {
-                        let ref tt = *t.clone(); // uhm ..
+                        let tt = &*t.clone(); // uhm ..
                         Ok(tt.clone())
                     },
+                    Type::Any => Ok(Type::Any),
+                    // the key might not be there
+                    Type::Map(ref k, ref v) => {
+                        let key = match **b {
+                            Expression::Identifier(_) => Type::Str,
+                            ref e                     => e.get_type(sym, env)?,
+                        };
+
+                        if !k.compare(&key) {
+                            return Err(ParserError::new(&format!("{}: expected key '{:?}', got '{:?}'", a, k, key)))
+                        }
+
+                        Ok(v.optional())
+                    },
+                    Type::Class(ref class, ref fields, ref methods, _) => match **b {
+                        Expression::Identifier(ref field) => match fields.iter().chain(methods.iter()).find(|&(n, _)| n == field) {
+                            Some((_, t)) => Ok(t.clone()),
+                            None              => Err(ParserError::new(&format!("{}: no field '{}' in '{}'", a, field, class))),
+                        },
+                        _ => Err(ParserError::new(&format!("{}: '{}' is indexed by field name, got '{}'", a, class, b))),
+                    },
+                    Type::Record(ref record, ref fields, _) | Type::Interface(ref record, ref fields) => match **b {
+                        Expression::Identifier(ref field) => match fields.iter().find(|&(n, _)| n == field) {
+                            Some((_, t)) => Ok(t.clone()),
+                            None              => Err(ParserError::new(&format!("{}: no field '{}' in '{}'", a, field, record))),
+                        },
+                        _ => Err(ParserError::new(&format!("{}: '{}' is indexed by field name, got '{}'", a, record, b))),
+                    },
+                    Type::Variant(ref e, ref variant) => match (&**e, &**b) {
+                        (Type::Enum(_, variants), Expression::Identifier(field)) => {
+                            let fields = &variants.iter().find(|&(v, _)| v == variant).unwrap().1;
+
+                            match fields.iter().find(|&(n, _)| n == field) {
+                                Some((_, t)) => Ok(t.clone()),
+                                None              => Err(ParserError::new(&format!("{}: no field '{}' in variant '{}'", a, field, variant))),
+                            }
+                        },
+                        _ => Err(ParserError::new(&format!("{}: variants are indexed by field name, got '{}'", a, b))),
+                    },
+                    Type::Enum(ref name, _) => Err(ParserError::new(&format!("{}: can't index '{}' before matching its variant", a, name))),
+                    Type::Optional(_) => Err(ParserError::new(&format!("{}: might be nil, check it first", a))),
                     _ => Err(ParserError::new(&format!("{:?}: trying to index '{:?}'", a, b)))
                 }
             },
 
-            Expression::Definition(ref t, _, ref expr) => {
+            Expression::Definition(ref t, _, ref expr, _) => {
                 match *t {
-                    Some(ref tp) => return Ok(tp.clone()),
-                    None         => if let &Some(ref e) = expr {
+                    Some(ref tp) => Ok(tp.clone()),
+                    None         => if let Some(e) = expr {
                         Ok(e.get_type(sym, env)?)
                     } else {
                         Ok(Type::Any)
@@ -66,52 +178,157 @@ impl Expression {
             },
 
             Expression::DictLiteral(ref content) => {
-                if let Some(t) = content.get(0) {
-                    Ok(Type::Array(Rc::new(t.get_type(&sym, &env)?)))
+                if Expression::is_keyed(content) {
+                    // a map when every value has the same type
+                    let mut value: Option<Type> = None;
+
+                    for e in content.iter() {
+                        let t = match *e {
+                            Expression::Definition(_, _, Some(ref v), _) => v.get_type(sym, env)?.widen(),
+                            _ => return Ok(Type::Any),
+                        };
+
+                        match value {
+                            Some(ref v) if *v != t => return Ok(Type::Any),
+                            _ => value = Some(t),
+                        }
+                    }
+
+                    Ok(Type::Map(Rc::new(Type::Str), Rc::new(value.unwrap_or(Type::Any))))
+                } else if let Some(t) = content.first() {
+                    Ok(Type::Array(Rc::new(t.get_type(sym, env)?)))
                 } else {
                     Ok(Type::Array(Rc::new(Type::Nil)))
                 }
             },
 
-            Expression::Fun { ref t, ref param_names, ref param_types, ref body, } => {
-                let mut tp = Vec::new();
-                
-                if let &Some(ref t) = t {
-                    tp.push(t.clone())
-                } else {
-                    tp.push(Type::Any)
+            Expression::Compound(ref target, _, _) => target.get_type(sym, env),
+
+            Expression::Fun { ref t, ref param_types, ref param_defaults, .. } => Ok(Type::fun(t, param_types, param_defaults)),
+            
+            Expression::Pipe(_, _) => self.desugar_pipe().get_type(sym, env),
+
+            // only the first of several values, like in lua
+            Expression::MethodCall(_, _, _) |
+            Expression::Call(_, _)          |
+            Expression::NamedCall { .. }    => Ok(self.get_values_type(sym, env)?.truncated()),
+            
+            // never makes a value, so it fits anywhere
+            Expression::Throw(_) => Ok(Type::Any),
+
+            Expression::Await(ref task) => Ok(task.get_type(sym, env)?.awaited()),
+
+            // the loop asks for the next value without passing anything
+            Expression::Yield(_) => Ok(Type::Nil),
+
+            Expression::Super { ref method, .. } => match *method {
+                Some(_) => match self.super_type(sym, env)? {
+                    Type::Fun(ref params, _) => Ok(params[0].clone()),
+                    _                        => Ok(Type::Any),
+                },
+                None => Ok(Type::Nil),
+            },
+
+            Expression::Operation { ref left, ref op, ref right, } => {
+                // the right side only runs when the left side was true, or false
+                let right_type = match *op {
+                    Operand::And => {
+                        let (local_sym, local_env) = narrowed(sym, env, &left.narrowings(sym, env, true)?)?;
+                        right.get_type(&local_sym, &local_env)?
+                    },
+                    Operand::Or => {
+                        let (local_sym, local_env) = narrowed(sym, env, &left.narrowings(sym, env, false)?)?;
+                        right.get_type(&local_sym, &local_env)?
+                    },
+                    _ => right.get_type(sym, env)?,
+                };
+
+                Ok(op.operate((left.get_type(sym, env)?.complete(sym), right_type.complete(sym)))?)
+            },
+
+            Expression::If(ref branches, ref otherwise) => {
+                let mut results = Vec::new();
+                let mut failed  = Vec::new();
+
+                let mut bodies = Vec::new();
+
+                for branch in branches.iter() {
+                    let mut narrowings = failed.clone();
+                    narrowings.extend(branch.condition.narrowings(sym, env, true)?);
+
+                    bodies.push((narrowings, branch.body.clone()));
+
+                    failed.extend(branch.condition.narrowings(sym, env, false)?)
                 }
-                
-                for t in param_types.iter() {
-                    tp.push(t.clone())
+
+                if let Some(ref body) = *otherwise {
+                    bodies.push((failed, body.clone()))
+                }
+
+                for (narrowings, body) in bodies {
+                    let (local_sym, local_env) = narrowed(sym, env, &narrowings)?;
+
+                    for statement in body.iter() {
+                        statement.visit(&local_sym, &local_env)?
+                    }
+
+                    let t = match body.last() {
+                        Some(s) => s.get_type(&local_sym, &local_env)?,
+                        None    => Type::Nil,
+                    };
+
+                    results.push(t)
+                }
+
+                match *otherwise {
+                    Some(_) => Ok(Type::union(results)),
+                    None    => Ok(Type::union(results).optional()),
                 }
-                
-                Ok(Type::Fun(Rc::new(tp)))
             },
-            
-            Expression::Call(ref id, ref args) => match id.get_type(sym, env)? {
-                Type::Fun(ref params) => Ok(params.get(0).unwrap().clone()),
-                Type::Any => Ok(Type::Any),
-                _         => Err(ParserError::new(&format!("{}: can't call non-fun", id))),
+
+            Expression::Match(ref value, ref arms) => {
+                let tp = value.get_type(sym, env)?;
+
+                let mut results = Vec::new();
+
+                for arm in arms.iter() {
+                    let local_sym = Rc::new(SymTab::new(sym.clone(), &Vec::new()));
+                    let local_env = Rc::new(TypeTab::new(env.clone(), &Vec::new()));
+
+                    arm.pattern.bind(&local_sym, &local_env, &tp)?;
+
+                    // the body's own locals are needed for its last statement
+                    for statement in arm.body.iter() {
+                        statement.visit(&local_sym, &local_env)?
+                    }
+
+                    let t = match arm.body.last() {
+                        Some(s) => s.get_type(&local_sym, &local_env)?,
+                        None    => Type::Nil,
+                    };
+
+                    results.push(t)
+                }
+
+                Ok(Type::union(results))
             },
             
-            Expression::Operation { ref left, ref op, ref right, } => Ok(op.operate((left.get_type(sym, env)?, right.get_type(sym, env)?))?),
-            
             _ => Ok(Type::Undefined),
         }
     }
     
     pub fn visit(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<()> {
         match *self {
-            Expression::Identifier(ref id) => match sym.get_name(&*id) {
+            Expression::Identifier(ref id) => match sym.get_name(id) {
                 Some(_) => {
                     Ok(())
                 },
                 None => Err(ParserError::new(&format!("use of undeclared: {}", id))),
             },
             
-            Expression::Index(ref a, ref b) => { 
-                a.visit(&sym, &env)?;
+            Expression::Index(ref a, _, _) => {
+                a.visit(sym, env)?;
+                self.get_type(sym, env)?;
 
                 Ok(())
             },
@@ -122,13 +339,15 @@ impl Expression {
 
                 let mut tp = Type::Any;
 
-                if let Some(t) = content.get(0) {
-                    tp = t.clone().get_type(&sym, &env)?;
+                if let Some(t) = content.first() {
+                    tp = t.clone().get_type(sym, env)?;
                 }
 
+                let keyed = Expression::is_keyed(content);
+
                 for s in content.iter() {
-                    let t = s.get_type(&sym, &env)?;
-                    if !tp.compare(&t) {
+                    let t = s.get_type(sym, env)?;
+                    if !keyed && !tp.compare(&t) {
                         return Err(ParserError::new(&format!("mismatched array type: expected '{:?}' got '{:?}'", tp, t)))
                     }
 
@@ -138,8 +357,8 @@ impl Expression {
                 Ok(())
             },
 
-            Expression::Definition(ref t, ref id, ref e) => {
-                if let &Some(ref expr) = e {
+            Expression::Definition(ref t, ref id, ref e, ref declared) => {
+                if let Some(expr) = e {
                     expr.visit(sym, env)?;
 
                     let tp = match *t {
@@ -152,56 +371,128 @@ impl Expression {
                         },
                         None => expr.get_type(sym, env)?,
                     };
-                    
-                    match **id {
-                        Expression::Identifier(ref name) => {
-                            match sym.get_name(&name) {
-                                Some((i, env_index)) => {
-                                    match env.get_type(i, env_index) {
-                                        Ok(tp2) => if !tp2.compare(&tp) {
-                                            return Err(ParserError::new(&format!("{}: can't mutate type", name)))
-                                        },
-                                        Err(e) => return Err(ParserError::new(&format!("{}", e))),
-                                    }
-                                },
-                                None => (),
-                            }
-                        
-                            let index = sym.add_name(name);
-                            if index >= env.size() {
-                                env.grow();
-                            }
-                            
-                            if let Err(e) = env.set_type(index, 0, tp) {
-                                Err(ParserError::new(&format!("error setting type: {}", e)))
-                            } else {
-                                Ok(())
-                            }
-                        },
 
-                        Expression::Index(ref a, ref b) => {
-                            a.visit(&sym, &env)?;
+                    *declared.borrow_mut() = id.declares(sym).into_iter().collect();
 
-                            Ok(())
-                        },
-                        
-                        _ => Err(ParserError::new(&format!("{}: failed to assign", id))),
-                    }
+                    id.assign_type(sym, env, tp)
                 } else {
                     Ok(())
                 }
             },
 
-            Expression::Fun { ref t, ref param_names, ref param_types, ref body, } => {
-                let local_sym = Rc::new(SymTab::new(sym.clone(), &param_names.as_slice()));
-                let local_env = Rc::new(TypeTab::new(env.clone(), &param_types));
+            Expression::MultiDefinition(ref targets, ref values, ref declared) => {
+                for value in values.iter() {
+                    value.visit(sym, env)?
+                }
 
-                for statement in body.iter() {
+                let mut types = Expression::list_types(values, sym, env)?;
+
+                // a last value of any type may be as many as are left
+                if let Some(last) = values.last() {
+                    if types.len() < targets.len() && last.get_values_type(sym, env)? == Type::Any {
+                        types.resize(targets.len(), Type::Any)
+                    }
+                }
+
+                if targets.len() != types.len() {
+                    return Err(ParserError::new(&format!("expected {} values, got {}", targets.len(), types.len())))
+                }
+
+                *declared.borrow_mut() = targets.iter().filter_map(|t| t.declares(sym)).collect();
+
+                for (target, tp) in targets.iter().zip(types) {
+                    target.assign_type(sym, env, tp)?
+                }
+
+                Ok(())
+            },
+
+            Expression::Destructure { ref names, ref value, ref keys, ref keyed, ref declared, } => {
+                value.visit(sym, env)?;
+
+                let t = value.get_type(sym, env)?.complete(sym);
+
+                let types = match *keys {
+                    Some(ref keys) => {
+                        let mut types = Vec::new();
+
+                        for key in keys.iter() {
+                            types.push(value.field_type(&t, key, sym, env)?)
+                        }
+
+                        types
+                    },
+
+                    None => match t {
+                        Type::Array(ref t) => vec![(**t).clone(); names.len()],
+                        Type::Any if !value.is_keyed_literal() => vec![Type::Any; names.len()],
+
+                        // [hp, name] takes the fields of the same names
+                        ref t => {
+                            let mut types = Vec::new();
+
+                            for name in names.iter() {
+                                types.push(value.field_type(t, name, sym, env)?)
+                            }
+
+                            keyed.set(true);
+
+                            types
+                        },
+                    },
+                };
+
+                *declared.borrow_mut() = names.iter().filter(|n| sym.get_name(n).is_none()).cloned().collect();
+
+                for (name, tp) in names.iter().zip(types) {
+                    Expression::Identifier(name.clone()).assign_type(sym, env, tp)?
+                }
+
+                Ok(())
+            },
+
+            Expression::Compound(ref target, ref op, ref expr) => {
+                match **target {
+                    Expression::Identifier(_) | Expression::Index(_, _, _) => target.visit(sym, env)?,
+                    _ => return Err(ParserError::new(&format!("{}: failed to assign", target))),
+                }
+
+                expr.visit(sym, env)?;
+
+                let tp     = target.get_type(sym, env)?;
+                let result = op.operate((tp.complete(sym), expr.get_type(sym, env)?.complete(sym)))?;
+
+                if !tp.compare(&result) {
+                    return Err(ParserError::new(&format!("{}: can't mutate type '{:?}' into '{:?}'", target, tp, result)))
+                }
+
+                Ok(())
+            },
+
+            Expression::Fun { t: ref self_t, ref param_names, ref param_types, ref param_defaults, ref body, } => {
+                let local_sym = Rc::new(SymTab::new(sym.clone(), param_names.as_slice()));
+                let local_env = Rc::new(TypeTab::new(env.clone(), &Type::locals(param_types, sym)));
+
+                visit_defaults(&local_sym, &local_env, param_names, param_types, param_defaults)?;
+
+                // an async body returns what awaiting it gives
+                let t = &self_t.as_ref().map(|t| t.awaited());
+
+                local_env.set_returns(t.clone().unwrap_or(Type::Any));
+                local_env.set_async(is_async(self_t));
+                local_env.set_yields(yielded(self_t));
+                local_env.set_fixed(Type::fun(self_t, param_types, param_defaults).vars());
+
+                for (i, statement) in body.iter().enumerate() {
                     statement.visit(&local_sym, &local_env)?;
 
-                    if let &Some(ref t) = t {
-                        let returned_type = statement.get_type(&local_sym, &local_env)?;
-                        if returned_type != *t {
+                    if i + 1 < body.len() || local_env.yields().is_some() {
+                        continue
+                    }
+
+                    if let Some(t) = t {
+                        let returned_type = statement.returned_type(t, &local_sym, &local_env)?;
+                        if !t.compare(&returned_type) {
                             match *t {
                                 Type::Any => (),
                                 _         => return Err(ParserError::new(&format!("fun mismatched return type: '{:?}' got '{:?}'", t, returned_type))),
@@ -215,48 +506,243 @@ impl Expression {
 
             Expression::Call(ref id, ref args) => {
                 match id.get_type(sym, env)? {
-                    Type::Fun(ref params) => {                        
+                    Type::Fun(ref params, required) => {                        
                         let mut arg_types = Vec::new();
 
                         for arg in args.iter() {
-                            arg_types.push(try!(arg.get_type(sym, env)));
+                            arg.visit(sym, env)?;
+                            arg_types.push(Some(arg.get_type(sym, env)?));
                         }
 
-                        match params[params.len() - 1] {
-                            Type::Many(ref t) => {
-                                if params[1..params.len() - 1].to_vec() != arg_types.as_slice()[1 .. params.len() - 1].to_vec() {
-                                    Err(ParserError::new(&format!("{}: supplied very wrong args", id)))
-                                } else {
-                                    for arg_t in arg_types[params.len() - 1 ..].iter() {
-                                        if !arg_t.compare(&**t) {
-                                            return Err(ParserError::new(&format!("{}: expected '{:?}' got '{:?}'", id, t, arg_t)))
-                                        }
-                                    }
-                                    Ok(())
-                                }
-                            },
-                            _ => if params[1..].to_vec() != arg_types.as_slice() {
-                                    Err(ParserError::new(&format!("{}: supplied very wrong args", id)))
-                                } else {
-                                    Ok(())
-                                },
-                        }
+                        let fixed: Vec<_> = ::std::iter::once(&**id).chain(args.iter()).map(|e| fixed_vars(e, sym, env)).collect();
+                        let (params, arg_types) = Type::instantiate(id, params, &arg_types, &fixed)?;
+
+                        check_args(id, &params, required, &arg_types)
                     },
 
-                    Type::Any => Ok(()),
+                    Type::Any => {
+                        for arg in args.iter() {
+                            arg.visit(sym, env)?
+                        }
+
+                        Ok(())
+                    },
 
                     _ => Err(ParserError::new(&format!("{}: calling non-funs is a sin", id))),
                 }
             }
 
+            Expression::Pipe(_, _) => self.desugar_pipe().visit(sym, env),
+
+            Expression::Throw(ref value) => value.visit(sym, env),
+
+            Expression::Await(ref task) => {
+                if !env.in_async() {
+                    return Err(ParserError::new(&format!("await {}: only async funs can wait", task)))
+                }
+
+                task.visit(sym, env)?;
+
+                match task.get_type(sym, env)? {
+                    Type::Async(_) | Type::Any => Ok(()),
+                    t => Err(ParserError::new(&format!("await {}: expected async, got '{:?}'", task, t))),
+                }
+            },
+
+            // the first yield decides what the generator gives when it's not declared
+            Expression::Yield(ref value) => {
+                value.visit(sym, env)?;
+
+                let t = value.get_type(sym, env)?;
+
+                match env.yields() {
+                    Some(Type::Undefined) => {
+                        env.yielded(t.widen());
+                        Ok(())
+                    },
+                    Some(ref expected) if !expected.compare(&t) => Err(ParserError::new(&format!("yield: expected '{:?}', got '{:?}'", expected, t))),
+                    _ => Ok(()),
+                }
+            },
+
+            Expression::Super { ref parent, ref method, ref args, } => {
+                let mut arg_types = Vec::new();
+
+                for arg in args.iter() {
+                    arg.visit(sym, env)?;
+                    arg_types.push(Some(arg.get_type(sym, env)?));
+                }
+
+                match (self.super_type(sym, env)?, method) {
+                    (Type::Fun(ref params, required), &None) => check_args(&Expression::Identifier(parent.clone()), params, required, &arg_types),
+
+                    // self is passed implicitly
+                    (Type::Fun(ref params, required), Some(method)) => {
+                        let mut rest = vec![params[0].clone()];
+                        rest.extend_from_slice(&params[2 ..]);
+
+                        check_args(&Expression::Identifier(method.clone()), &rest, required.saturating_sub(1), &arg_types)
+                    },
+
+                    (t, _) => Err(ParserError::new(&format!("{}: can't call super of '{:?}'", parent, t))),
+                }
+            },
+
+            Expression::MethodCall(ref receiver, ref method, ref args) => {
+                receiver.visit(sym, env)?;
+
+                let mut arg_types = Vec::new();
+
+                for arg in args.iter() {
+                    arg.visit(sym, env)?;
+                    arg_types.push(Some(arg.get_type(sym, env)?));
+                }
+
+                match receiver.method_type(method, sym, env)? {
+                    Type::Fun(ref params, required) => {
+                        if params.len() < 2 {
+                            return Err(ParserError::new(&format!("{}::{}: method takes no self", receiver, method)))
+                        }
+
+                        let mut with_self = vec![Some(receiver.get_type(sym, env)?)];
+                        with_self.extend(arg_types.iter().cloned());
+
+                        let id = Expression::Identifier(method.clone());
+                        // a method's type params are its own
+                        let mut fixed = vec![Vec::new()];
+                        fixed.extend(::std::iter::once(&**receiver).chain(args.iter()).map(|e| fixed_vars(e, sym, env)));
+
+                        let (params, with_self) = Type::instantiate(&id, params, &with_self, &fixed)?;
+
+                        // self is passed implicitly
+                        let mut rest = vec![params[0].clone()];
+                        rest.extend_from_slice(&params[2 ..]);
+
+                        check_args(&id, &rest, required.saturating_sub(1), &with_self[1 ..])
+                    },
+
+                    Type::Any => Ok(()),
+
+                    _ => Err(ParserError::new(&format!("{}::{}: calling non-funs is a sin", receiver, method))),
+                }
+            },
+
+            Expression::NamedCall { ref id, ref args, ref named, ref order, } => {
+                let param_names = match **id {
+                    Expression::Identifier(ref name) => match sym.get_params(name) {
+                        Some(p) => p,
+                        None    => return Err(ParserError::new(&format!("{}: can't name args of unknown fun", id))),
+                    },
+                    _ => return Err(ParserError::new(&format!("{}: can't name args of unknown fun", id))),
+                };
+
+                if args.len() > param_names.len() {
+                    return Err(ParserError::new(&format!("{}: expected at most {} args, got {}", id, param_names.len(), args.len())))
+                }
+
+                let mut slots: Vec<Option<usize>> = (0 .. args.len()).map(Some).collect();
+                slots.resize(param_names.len(), None);
+
+                for (n, (name, _)) in named.iter().enumerate() {
+                    let i = match param_names.iter().position(|p| p == name) {
+                        Some(i) => i,
+                        None    => return Err(ParserError::new(&format!("{}: unknown arg '{}'", id, name))),
+                    };
+
+                    if slots[i].is_some() {
+                        return Err(ParserError::new(&format!("{}: arg '{}' supplied twice", id, name)))
+                    }
+
+                    slots[i] = Some(args.len() + n)
+                }
+
+                while let Some(&None) = slots.last() {
+                    slots.pop();
+                }
+
+                let values = self.named_values();
+
+                let mut arg_types = Vec::new();
+
+                for slot in slots.iter() {
+                    arg_types.push(match *slot {
+                        Some(i) => {
+                            let arg = values[i];
+
+                            arg.visit(sym, env)?;
+                            Some(arg.get_type(sym, env)?)
+                        },
+                        None => None,
+                    })
+                }
+
+                match id.get_type(sym, env)? {
+                    Type::Fun(ref params, required) => {
+                        let mut fixed = vec![fixed_vars(id, sym, env)];
+                        fixed.extend(slots.iter().map(|slot| slot.map_or_else(Vec::new, |i| fixed_vars(values[i], sym, env))));
+
+                        let (params, arg_types) = Type::instantiate(id, params, &arg_types, &fixed)?;
+
+                        check_args(id, &params, required, &arg_types)?
+                    },
+                    _ => return Err(ParserError::new(&format!("{}: calling non-funs is a sin", id))),
+                }
+
+                *order.borrow_mut() = slots;
+
+                Ok(())
+            },
+
+            Expression::If(ref branches, ref otherwise) => {
+                let mut failed = Vec::new();
+
+                for branch in branches.iter() {
+                    let (cond_sym, cond_env) = narrowed(sym, env, &failed)?;
+
+                    branch.condition.visit(&cond_sym, &cond_env)?;
+
+                    let t = branch.condition.get_type(&cond_sym, &cond_env)?;
+                    if !Type::Bool.compare(&t) {
+                        return Err(ParserError::new(&format!("{}: expected 'Bool' condition, got '{:?}'", branch.condition, t)))
+                    }
+
+                    let mut narrowings = failed.clone();
+                    narrowings.extend(branch.condition.narrowings(&cond_sym, &cond_env, true)?);
+
+                    let (local_sym, local_env) = narrowed(sym, env, &narrowings)?;
+
+                    for statement in branch.body.iter() {
+                        statement.visit(&local_sym, &local_env)?
+                    }
+
+                    failed.extend(branch.condition.narrowings(&cond_sym, &cond_env, false)?)
+                }
+
+                if let Some(ref body) = *otherwise {
+                    let (local_sym, local_env) = narrowed(sym, env, &failed)?;
+
+                    for statement in body.iter() {
+                        statement.visit(&local_sym, &local_env)?
+                    }
+                }
+
+                Ok(())
+            },
+
+            // the arms are lowered into a closure, where a return only leaves the closure
+            Expression::Match(ref value, ref arms) => match arms.iter().find(|arm| returns_within(&arm.body)) {
+                Some(arm) => Err(ParserError::new(&format!("{}: can't return from a match used as a value", arm.pattern))),
+                None      => visit_match(value, arms, sym, env),
+            },
+
             _ => Ok(())
         }
     }
     
     pub fn global(&self) -> Rc<String> {
         match *self {
-            Expression::Definition(_, ref name, ref expr) => {
-                if let &Some(ref e) = expr {
+            Expression::Definition(_, ref name, ref expr, _) => {
+                if let Some(e) = expr {
                     Rc::new(format!("{} = {}", name, e))
                 } else {
                     Rc::new(format!("{}", name))
@@ -280,29 +766,130 @@ impl Expression {
             Expression::NumberLiteral(ref n) => write!(f, "{}", n),
             Expression::StringLiteral(ref n) => write!(f, "\"{}\"", n),
             Expression::BoolLiteral(ref n)   => write!(f, "{}", n),
+            Expression::NilLiteral           => write!(f, "nil"),
             Expression::Identifier(ref n)    => write!(f, "{}", n),
-            Expression::Definition(_, ref name, ref expr) => {
-                match **name {
-                    Expression::Index(_, _) => {
-                        if let &Some(ref e) = expr {
-                            writeln!(f, "{} = {}", name, e)?;
-                        }
+            Expression::Definition(_, ref name, ref expr, ref declared) => {
+                let mut targets  = vec![name.clone()];
+                let mut declared = declared.borrow().clone();
+                let mut value    = match *expr {
+                    Some(ref e) => e.clone(),
+                    None        => return writeln!(f, "local {}", name),
+                };
+
+                // a = b = c: collect targets until the actual value
+                while let Expression::Definition(_, ref inner, Some(ref e), ref inner_declared) = *value.clone() {
+                    targets.push(inner.clone());
+                    declared.extend(inner_declared.borrow().iter().cloned());
+                    value = e.clone()
+                }
+
+                let source = match *value {
+                    Expression::Compound(ref inner, _, _) => {
+                        write!(f, "{}", value)?;
+                        format!("{}", inner)
                     },
-                    _ => (),
+                    _ => format!("{}", value),
+                };
+
+                if targets.len() == 1 {
+                    return Expression::lua_assign(f, name, &source, &declared)
                 }
-                if let &Some(ref e) = expr {
-                    writeln!(f, "local {} = {}", name, e)
-                } else {
-                    writeln!(f, "local {}", name)
+
+                let last = targets.pop().unwrap();
+
+                let source = match *last {
+                    Expression::Identifier(_) => {
+                        Expression::lua_assign(f, &last, &source, &declared)?;
+                        format!("{}", last)
+                    },
+                    _ => {
+                        let tmp = temp_name("value");
+                        writeln!(f, "local {} = {}", tmp, source)?;
+                        Expression::lua_assign(f, &last, &tmp, &declared)?;
+                        tmp
+                    },
+                };
+
+                for target in targets.iter().rev() {
+                    Expression::lua_assign(f, target, &source, &declared)?
                 }
+
+                Ok(())
             },
-            
-            Expression::Index(ref a, ref b) => {
-                match **b {
-                    Expression::Identifier(_) => write!(f, "{}.{}", a, b),
-                    _ => write!(f, "{}[{}]", a, b),
+
+            Expression::MultiDefinition(ref targets, ref values, ref declared) => {
+                let locals = targets.iter().all(|t| matches!(*t, Expression::Identifier(_)));
+
+                if locals {
+                    return Expression::lua_assign_list(f, targets, &declared.borrow(), &Expression::lua_list(values))
                 }
-            },
+
+                let temps: Vec<String> = targets.iter().map(|_| temp_name("value")).collect();
+
+                writeln!(f, "local {} = {}", temps.join(", "), Expression::lua_list(values))?;
+
+                for (target, tmp) in targets.iter().zip(temps.iter()) {
+                    Expression::lua_assign(f, target, tmp, &declared.borrow())?
+                }
+
+                Ok(())
+            },
+
+            Expression::Destructure { ref names, ref value, ref keys, ref keyed, ref declared, } => {
+                let source = if value.is_simple() {
+                    format!("{}", value)
+                } else {
+                    let tmp = temp_name("value");
+                    writeln!(f, "local {} = {}", tmp, value)?;
+                    tmp
+                };
+
+                let fields: Vec<String> = match *keys {
+                    Some(ref keys)     => keys.iter().map(|key| format!("{}.{}", source, key)).collect(),
+                    None if keyed.get() => names.iter().map(|name| format!("{}.{}", source, name)).collect(),
+                    None               => (1 ..= names.len()).map(|i| format!("{}[{}]", source, i)).collect(),
+                };
+
+                Expression::lua_assign_list(f, names, &declared.borrow(), &fields.join(", "))
+            },
+
+            Expression::Compound(ref target, ref op, ref value) => {
+                let value = match **value {
+                    Expression::Operation { .. } => format!("({})", value),
+                    _                            => format!("{}", value),
+                };
+
+                match **target {
+                    Expression::Index(ref a, ref b, _) if !a.is_simple() || !b.is_simple() => {
+                        let object = temp_name("object");
+
+                        writeln!(f, "do")?;
+                        writeln!(f, "local {} = {}", object, a)?;
+
+                        let target = match **b {
+                            Expression::Identifier(_) => format!("{}.{}", object, b),
+                            _ if b.is_simple()        => format!("{}[{}]", object, b),
+                            _ => {
+                                let key = temp_name("key");
+                                writeln!(f, "local {} = {}", key, b)?;
+                                format!("{}[{}]", object, key)
+                            },
+                        };
+
+                        writeln!(f, "{} = {} {} {}", target, target, op, value)?;
+                        writeln!(f, "end")
+                    },
+
+                    _ => writeln!(f, "{} = {} {} {}", target, target, op, value),
+                }
+            },
+            
+            Expression::Index(ref a, ref b, _) => {
+                match **b {
+                    Expression::Identifier(_) => write!(f, "{}.{}", a, b),
+                    _ => write!(f, "{}[{}]", a, b),
+                }
+            },
 
             Expression::DictLiteral(ref body)  => {
                 write!(f, "{{")?;
@@ -314,202 +901,2270 @@ impl Expression {
                 write!(f, "}}")
             },
 
-            Expression::Call(ref id, ref args) => {
-                write!(f, "{}", id)?;
-                write!(f, "(")?;
+            Expression::Call(ref id, ref args) => {
+                write!(f, "{}", id)?;
+                write!(f, "(")?;
+
+                for (i, e) in args.iter().enumerate() {
+                    write!(f, "{}", e)?;
+                    if i + 1 != args.len() {
+                        write!(f, ",")?;
+                    }
+                }
+
+                write!(f, ")")
+            },
+
+            Expression::Pipe(ref left, ref right) => {
+                let rest_pure = match **right {
+                    Expression::Call(ref id, ref args)   => id.is_pure() && args.iter().all(|a| a.is_pure()),
+                    Expression::NamedCall { ref id, .. } => id.is_pure() && right.named_values().iter().all(|a| a.is_pure()),
+                    _ => return write!(f, "{}", right),
+                };
+
+                // the left side has to run before the callee and the other args
+                if left.is_pure() || rest_pure {
+                    return write!(f, "{}", self.desugar_pipe())
+                }
+
+                let tmp   = temp_name("pipe");
+                let piped = Expression::Pipe(Rc::new(Expression::Identifier(Rc::new(tmp.clone()))), right.clone()).desugar_pipe();
+
+                write!(f, "(function({}) return {} end)({})", tmp, piped, left)
+            },
+
+            Expression::Throw(ref value) => write!(f, "error({}, 0)", value),
+
+            Expression::Await(ref task) => {
+                use_async();
+                write!(f, "__async.await({})", task)
+            },
+
+            Expression::Yield(ref value) => write!(f, "coroutine.yield({})", value),
+
+            Expression::Super { ref parent, ref method, ref args, } => {
+                let mut with_self = vec!["self".to_string()];
+                with_self.extend(args.iter().map(|a| format!("{}", a)));
+
+                match *method {
+                    Some(ref m) => write!(f, "{}.{}({})", parent, m, with_self.join(",")),
+                    None        => write!(f, "{}.init({})", parent, with_self.join(",")),
+                }
+            },
+
+            Expression::MethodCall(ref receiver, ref method, ref args) => {
+                match **receiver {
+                    Expression::Identifier(_) | Expression::Index(_, _, _) | Expression::Call(_, _) => write!(f, "{}", receiver)?,
+                    _ => write!(f, "({})", receiver)?,
+                }
+
+                write!(f, ":{}({})", method, Expression::lua_list(args))
+            },
+
+            Expression::NamedCall { ref id, ref order, .. } => {
+                let values = self.named_values();
+                let order  = order.borrow();
+
+                // putting them in param order mustn't reorder what they do
+                let effects: Vec<usize> = (0 .. values.len()).filter(|&i| !values[i].is_pure()).collect();
+                let moved = order.iter().flatten().filter(|&&i| !values[i].is_pure()).ne(effects.iter());
+
+                let temps: Vec<String> = if moved {
+                    effects.iter().map(|_| temp_name("arg")).collect()
+                } else {
+                    Vec::new()
+                };
+
+                let args: Vec<String> = order.iter().map(|slot| match *slot {
+                    Some(i) => match effects.iter().position(|&e| e == i) {
+                        Some(t) if moved => temps[t].clone(),
+                        _                => format!("{}", values[i]),
+                    },
+                    None => "nil".to_string(),
+                }).collect();
+
+                if !moved {
+                    return write!(f, "{}({})", id, args.join(","))
+                }
+
+                let hoisted: Vec<String> = effects.iter().map(|&i| format!("{}", values[i])).collect();
+
+                write!(f, "(function({}) return {}({}) end)({})", temps.join(","), id, args.join(","), hoisted.join(","))
+            },
+
+            Expression::Fun {
+                ref param_names, ref param_types, ref param_defaults, ref body, ref t,
+            } => lua_fun(f, None, param_names, param_types, param_defaults, t, body),
+
+            Expression::If(ref branches, ref otherwise) => {
+                writeln!(f, "(function()")?;
+                lua_if(f, branches, otherwise, true)?;
+                write!(f, "end)()")
+            },
+
+            Expression::Match(ref value, ref arms) => {
+                let tmp = temp_name("match");
+
+                writeln!(f, "(function({})", tmp)?;
+                lua_arms(f, &tmp, arms, true)?;
+                write!(f, "end)({})", value)
+            },
+            
+            Expression::Operation {
+                ref left, ref op, ref right,
+            } => {
+                for (i, e) in [left, right].iter().enumerate() {
+                    if i == 1 {
+                        write!(f, " {} ", op)?;
+                    }
+
+                    match ***e {
+                        Expression::Operation { .. } => write!(f, "({})", e)?,
+                        _                            => write!(f, "{}", e)?,
+                    }
+                }
+
+                Ok(())
+            },
+
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Expression {
+    // the type of a field taken out of this value, of type t
+    fn field_type(&self, t: &Type, key: &str, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+        // keyed literals know each of their fields
+        if let Expression::DictLiteral(ref content) = *self {
+            for e in content.iter() {
+                if let Expression::Definition(_, ref name, Some(ref v), _) = *e {
+                    if let Expression::Identifier(ref name) = **name {
+                        if name.as_str() == key {
+                            return Ok(v.get_type(sym, env)?.widen())
+                        }
+                    }
+                }
+            }
+        }
+
+        match *t {
+            Type::Any => Ok(Type::Any),
+            Type::Map(ref k, ref v) if k.compare(&Type::Str) => Ok(v.optional()),
+            _ => match t.field(key) {
+                Some(t) => Ok(t),
+                None    => Err(ParserError::new(&format!("{}: no field '{}' to destructure", self, key))),
+            },
+        }
+    }
+
+    // checks and records the type being assigned to a target
+    pub fn assign_type(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>, tp: Type) -> ParserResult<()> {
+        match *self {
+            Expression::Identifier(ref name) => {
+                // checked against the declared type, even where it's narrowed
+                if let Some((i, env_index)) = sym.get_declared_name(name) {
+                    match env.get_type(i, env_index) {
+                        Ok(tp2) => if !tp2.compare(&tp) {
+                            return Err(ParserError::new(&format!("{}: can't mutate type", name)))
+                        },
+                        Err(e) => return Err(ParserError::new(&format!("{}", e))),
+                    }
+                }
+            
+                declare(sym, env, name, tp.widen())
+            },
+
+            Expression::Index(ref a, _, _) => {
+                a.visit(sym, env)?;
+
+                let target = self.get_type(sym, env)?;
+                if !target.compare(&tp) {
+                    return Err(ParserError::new(&format!("{}: expected '{:?}', got '{:?}'", self, target, tp)))
+                }
+
+                Ok(())
+            },
+            
+            _ => Err(ParserError::new(&format!("{}: failed to assign", self))),
+        }
+    }
+
+    // x |> f(a) is f(x, a)
+    pub fn desugar_pipe(&self) -> Expression {
+        match *self {
+            Expression::Pipe(ref left, ref right) => match **right {
+                Expression::Call(ref id, ref args) => {
+                    let mut piped = vec![(**left).clone()];
+                    piped.extend(args.iter().cloned());
+
+                    Expression::Call(id.clone(), Rc::new(piped))
+                },
+                Expression::NamedCall { ref id, ref args, ref named, ref order, } => {
+                    let mut piped = vec![(**left).clone()];
+                    piped.extend(args.iter().cloned());
+
+                    Expression::NamedCall {
+                        id:    id.clone(),
+                        args:  Rc::new(piped),
+                        named: named.clone(),
+                        order: order.clone(),
+                    }
+                },
+                _ => self.clone(),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    // names known to have a narrower type when this condition is true, or false
+    pub fn narrowings(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>, holds: bool) -> ParserResult<Vec<(Rc<String>, Type)>> {
+        match *self {
+            Expression::Operation { ref left, ref op, ref right, } => {
+                let (name, literal) = match (&**left, &**right) {
+                    (Expression::Identifier(name), literal) if literal.is_literal() => (name, literal),
+                    (literal, Expression::Identifier(name)) if literal.is_literal() => (name, literal),
+
+                    _ => return match (op, holds) {
+                        (&Operand::And, true) | (&Operand::Or, false) => {
+                            let mut narrowings = left.narrowings(sym, env, holds)?;
+
+                            let (local_sym, local_env) = narrowed(sym, env, &narrowings)?;
+                            narrowings.extend(right.narrowings(&local_sym, &local_env, holds)?);
+
+                            Ok(narrowings)
+                        },
+                        _ => Ok(Vec::new()),
+                    },
+                };
+
+                let equal = match (op, holds) {
+                    (&Operand::Equal, true) | (&Operand::NEqual, false) => true,
+                    (&Operand::NEqual, true) | (&Operand::Equal, false) => false,
+                    _ => return Ok(Vec::new()),
+                };
+
+                let t = Expression::Identifier(name.clone()).get_type(sym, env)?;
+
+                let narrowed = match (literal, equal) {
+                    (&Expression::NilLiteral, false) => match t {
+                        Type::Optional(ref t) => (**t).clone(),
+                        _                     => return Ok(Vec::new()),
+                    },
+                    (&Expression::NilLiteral, true) => return Ok(Vec::new()),
+                    (_, true)  => t.narrow(&literal.get_type(sym, env)?),
+                    (_, false) => return Ok(Vec::new()),
+                };
+
+                if narrowed == t {
+                    return Ok(Vec::new())
+                }
+
+                Ok(vec![(name.clone(), narrowed)])
+            },
+
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    // every value an expression results in, calls may return several
+    pub fn get_values_type(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+        match *self {
+            Expression::MethodCall(ref receiver, ref method, _) => match receiver.method_type(method, sym, env)? {
+                Type::Fun(ref params, _) => self.returned(params, sym, env),
+                _                        => Ok(Type::Any),
+            },
+
+            Expression::Call(ref id, _) | Expression::NamedCall { ref id, .. } => match id.get_type(sym, env)? {
+                Type::Fun(ref params, _) => self.returned(params, sym, env),
+                Type::Any => Ok(Type::Any),
+                _         => Err(ParserError::new(&format!("{}: can't call non-fun", id))),
+            },
+
+            Expression::Pipe(_, _) => self.desugar_pipe().get_values_type(sym, env),
+
+            _ => self.get_type(sym, env),
+        }
+    }
+
+    // the types of a list of values, where only the last one may expand
+    pub fn list_types(values: &[Expression], sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Vec<Type>> {
+        let mut types = Vec::new();
+
+        for (i, value) in values.iter().enumerate() {
+            if i + 1 < values.len() {
+                types.push(value.get_type(sym, env)?);
+                continue
+            }
+
+            match value.get_values_type(sym, env)? {
+                Type::Tuple(ref members) => types.extend(members.iter().cloned()),
+                t                        => types.push(t),
+            }
+        }
+
+        Ok(types)
+    }
+
+    // what a call returns, with type params taken from its args
+    fn returned(&self, params: &[Type], sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+        if !params.iter().any(|p| p.is_generic()) {
+            return Ok(params[0].clone())
+        }
+
+        let args: Vec<Expression> = match *self {
+            Expression::Call(_, ref args) => args.to_vec(),
+            Expression::NamedCall { ref args, ref order, .. } => if order.borrow().is_empty() {
+                args.to_vec()
+            } else {
+                let values = self.named_values();
+
+                order.borrow().iter().map(|slot| match *slot {
+                    Some(i) => values[i].clone(),
+                    None    => Expression::Eof,
+                }).collect()
+            },
+            Expression::MethodCall(ref receiver, _, ref args) => {
+                let mut with_self = vec![(**receiver).clone()];
+                with_self.extend(args.iter().cloned());
+                with_self
+            },
+            _ => Vec::new(),
+        };
+
+        let mut arg_types = Vec::new();
+
+        for arg in args.iter() {
+            arg_types.push(match *arg {
+                Expression::Eof => None,
+                ref e           => Some(e.get_type(sym, env)?),
+            })
+        }
+
+        // a method's type params are its own
+        let mut fixed = vec![match *self {
+            Expression::Call(ref id, _) | Expression::NamedCall { ref id, .. } => fixed_vars(id, sym, env),
+            _ => Vec::new(),
+        }];
+        fixed.extend(args.iter().map(|e| fixed_vars(e, sym, env)));
+
+        Ok(Type::instantiate(self, params, &arg_types, &fixed)?.0[0].clone())
+    }
+
+    pub fn method_type(&self, method: &Rc<String>, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+        Expression::Index(Rc::new(self.clone()), Rc::new(Expression::Identifier(method.clone())), Rc::new(Cell::new(false))).get_type(sym, env)
+    }
+
+    // the parent's constructor or method that super calls
+    fn super_type(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+        match *self {
+            Expression::Super { ref parent, ref method, .. } => {
+                let name = match *method {
+                    Some(ref m) => m.clone(),
+                    None        => Rc::new("new".to_string()),
+                };
+
+                Expression::Identifier(parent.clone()).method_type(&name, sym, env)
+            },
+            _ => Ok(Type::Any),
+        }
+    }
+
+    // the values of a named call as written, positional ones first
+    fn named_values(&self) -> Vec<&Expression> {
+        match *self {
+            Expression::NamedCall { ref args, ref named, .. } => args.iter().chain(named.iter().map(|(_, e)| e)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_keyed(content: &[Expression]) -> bool {
+        content.iter().any(|e| matches!(*e, Expression::Definition(_, _, _, _)))
+    }
+
+    fn is_keyed_literal(&self) -> bool {
+        match *self {
+            Expression::DictLiteral(ref content) => Expression::is_keyed(content),
+            _ => false,
+        }
+    }
+
+    // evaluating these more than once has no side effects
+    pub fn is_simple(&self) -> bool {
+        matches!(*self,
+            Expression::Identifier(_)    |
+            Expression::NumberLiteral(_) |
+            Expression::StringLiteral(_) |
+            Expression::BoolLiteral(_)   |
+            Expression::NilLiteral
+        )
+    }
+
+    pub fn is_literal(&self) -> bool {
+        matches!(*self,
+            Expression::NumberLiteral(_) |
+            Expression::StringLiteral(_) |
+            Expression::BoolLiteral(_)   |
+            Expression::NilLiteral
+        )
+    }
+
+    // no side effects and no metamethods to run out of order
+    pub fn is_pure(&self) -> bool {
+        match *self {
+            Expression::Index(ref a, ref b, ref custom) => !custom.get() && a.is_pure() && b.is_pure(),
+            _                               => self.is_simple(),
+        }
+    }
+
+    fn lua_list<T: fmt::Display>(items: &[T]) -> String {
+        items.iter().map(|e| format!("{}", e)).collect::<Vec<String>>().join(", ")
+    }
+
+    // only names the checker declared here are new locals, the rest are assigned
+    fn lua_assign(f: &mut fmt::Formatter, target: &Expression, value: &str, declared: &[Rc<String>]) -> fmt::Result {
+        match *target {
+            Expression::Identifier(ref name) if declared.contains(name) => writeln!(f, "local {} = {}", target, value),
+            _ => writeln!(f, "{} = {}", target, value),
+        }
+    }
+
+    // a, b = values, declaring the new names first when some are already in scope
+    fn lua_assign_list<T: fmt::Display>(f: &mut fmt::Formatter, names: &[T], declared: &[Rc<String>], values: &str) -> fmt::Result {
+        if names.len() == declared.len() {
+            return writeln!(f, "local {} = {}", Expression::lua_list(names), values)
+        }
+
+        if !declared.is_empty() {
+            writeln!(f, "local {}", Expression::lua_list(declared))?
+        }
+
+        writeln!(f, "{} = {}", Expression::lua_list(names), values)
+    }
+
+    // the name assigning to this declares, when it isn't in scope yet
+    fn declares(&self, sym: &Rc<SymTab>) -> Option<Rc<String>> {
+        match *self {
+            Expression::Identifier(ref name) if sym.get_name(name).is_none() => Some(name.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.lua(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Expression(Rc<Expression>),
+    Fun {
+        name:           Rc<String>,
+        param_names:    Rc<Vec<Rc<String>>>,
+        param_types:    Rc<Vec<Type>>,
+        param_defaults: Rc<Vec<Option<Rc<Expression>>>>,
+        t:              Option<Type>,
+        body:           Rc<Vec<Statement>>,
+    },
+    Record {
+        name:   Rc<String>,
+        fields: Fields,
+        ops:    Rc<Vec<Statement>>, // funs named by their metamethod
+    },
+    Enum {
+        name:     Rc<String>,
+        variants: Rc<Vec<(Rc<String>, Fields)>>,
+    },
+    Interface {
+        name:   Rc<String>,
+        fields: Fields,
+    },
+    Alias {
+        name: Rc<String>,
+        t:    Type,
+    },
+    Return(Rc<Vec<Expression>>),
+    For {
+        names:    Rc<Vec<Rc<String>>>,
+        iterable: Rc<Expression>,
+        body:     Rc<Vec<Statement>>,
+        over:     Rc<Cell<Iteration>>, // set by visit
+    },
+    Import {
+        path: Rc<String>, // ui.button
+        name: Rc<String>,
+    },
+    Export(Rc<Statement>),
+    Error(Rc<Vec<Rc<String>>>), // where parsing failed, the error is a diagnostic; with the names it declares
+    Try {
+        body:    Rc<Vec<Statement>>,
+        catch:   Option<Catch>,
+        finally: Option<Rc<Vec<Statement>>>,
+    },
+    Class {
+        name:    Rc<String>,
+        parent:  Option<Rc<String>>,
+        t:       Type,
+        methods: Rc<Vec<Statement>>, // funs, new is the constructor
+        base:    Fields, // what the parent's new takes, passed on by a generated one
+    },
+}
+
+// how a for loop goes through what it's given
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Iteration {
+    Array, Map, Iter,
+}
+
+impl Statement {
+    pub fn visit(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<()> {
+        match *self {
+            Statement::Expression(ref e) => match **e {
+                Expression::Match(ref value, ref arms) => visit_match(value, arms, sym, env),
+                _ => e.visit(sym, env),
+            },
+            Statement::Fun { ref name, ref t, ref param_names, ref param_types, ref param_defaults, ref body, } => {
+                match sym.get_name(name) {
+                    Some((_, _)) => return Err(ParserError::new(&format!("{}: already declared", name))),
+                    None => {
+                        let index = sym.add_name(name);
+                        if index >= env.size() {
+                            env.grow();
+                        }
+
+                        sym.add_params(name, param_names.clone());
+
+                        if let Err(e) = env.set_type(index, 0, self.get_type(sym, env)?) {
+                            panic!("error setting type: {}", e)
+                        }
+                    },
+                }
+
+                let local_sym = Rc::new(SymTab::new(sym.clone(), param_names));
+                let local_env = Rc::new(TypeTab::new(env.clone(), &Type::locals(param_types, sym)));
+
+                visit_defaults(&local_sym, &local_env, param_names, param_types, param_defaults)?;
+
+                local_env.set_async(is_async(t));
+                local_env.set_yields(yielded(t));
+                local_env.set_fixed(Type::fun(t, param_types, param_defaults).vars());
+
+                // an async body returns what awaiting it gives
+                let t = &t.as_ref().map(|t| t.awaited());
+
+                local_env.set_returns(t.clone().unwrap_or(Type::Any));
+
+                for (i, statement) in body.iter().enumerate() {
+                    statement.visit(&local_sym, &local_env)?;
+
+                    // only the last statement is returned, generators return nothing
+                    if i + 1 < body.len() || local_env.yields().is_some() {
+                        continue
+                    }
+
+                    if let Some(t) = t {
+                        if !t.compare(&statement.returned_type(t, &local_sym, &local_env)?) {
+                            match *t {
+                                Type::Any => (),
+                                _ => return Err(ParserError::new(&format!("{}: mismatched return type", name))),
+                            }
+                        }
+                    }
+                }
+
+                // callers see what the body yields
+                if let Some(elem) = local_env.yields() {
+                    let elem = match elem {
+                        Type::Undefined => Type::Any,
+                        t               => t,
+                    };
+
+                    let (index, env_index) = sym.get_name(name).unwrap();
+                    let t = Type::fun(&Some(Type::Iter(Rc::new(elem))), param_types, param_defaults);
+
+                    if let Err(e) = env.set_type(index, env_index, t) {
+                        panic!("error setting type: {}", e)
+                    }
+                }
+
+                Ok(())
+            },
+
+            Statement::Record { ref name, ref fields, ref ops, } => {
+                if sym.get_name(name).is_some() {
+                    return Err(ParserError::new(&format!("{}: already declared", name)))
+                }
+
+                // the constructor takes the fields in order, or by name
+                declare(sym, env, name, self.get_type(sym, env)?)?;
+                sym.add_params(name, Rc::new(fields.iter().map(|(n, _)| n.clone()).collect()));
+
+                let t = Type::Record(name.clone(), fields.clone(), Rc::new(ops.iter().filter_map(|o| o.method_signature()).collect()));
+                sym.add_definition(name, t.clone());
+
+                for op in ops.iter() {
+                    op.visit_method(name, &t, sym, env)?
+                }
+
+                Ok(())
+            },
+
+            Statement::Enum { ref name, .. } => {
+                if sym.get_name(name).is_some() {
+                    return Err(ParserError::new(&format!("{}: already declared", name)))
+                }
+
+                declare(sym, env, name, self.get_type(sym, env)?)
+            },
+
+            // only known to the type checker
+            Statement::Interface { .. } | Statement::Alias { .. } => Ok(()),
+
+            Statement::Return(ref values) => {
+                for value in values.iter() {
+                    value.visit(sym, env)?
+                }
+
+                let returned = Type::tuple(Expression::list_types(values, sym, env)?);
+
+                match env.returns() {
+                    Some(ref t) if !t.compare(&returned) => Err(ParserError::new(&format!("return: expected '{:?}', got '{:?}'", t, returned))),
+                    _ => Ok(()),
+                }
+            },
+
+            Statement::For { ref names, ref iterable, ref body, ref over, } => {
+                iterable.visit(sym, env)?;
+
+                let (key, value) = match iterable.get_type(sym, env)? {
+                    Type::Array(ref t) => {
+                        over.set(Iteration::Array);
+                        (Type::Num, (**t).clone())
+                    },
+                    Type::Map(ref k, ref v) => {
+                        over.set(Iteration::Map);
+                        ((**k).clone(), (**v).clone())
+                    },
+                    Type::Iter(ref t) => {
+                        over.set(Iteration::Iter);
+                        (Type::Nil, (**t).clone())
+                    },
+                    Type::Any => {
+                        over.set(Iteration::Map);
+                        (Type::Any, Type::Any)
+                    },
+                    t => return Err(ParserError::new(&format!("{}: can't iterate '{:?}'", iterable, t))),
+                };
+
+                // a single name gets the values of arrays and the keys of maps
+                let types = match (names.len(), over.get()) {
+                    (1, Iteration::Map) => vec![key],
+                    (1, _)              => vec![value],
+                    (_, Iteration::Iter) => return Err(ParserError::new(&format!("{}: generators give one value", iterable))),
+                    _                   => vec![key, value],
+                };
+
+                let local_sym = Rc::new(SymTab::new(sym.clone(), names));
+                let local_env = Rc::new(TypeTab::new(env.clone(), &types));
+
+                for statement in body.iter() {
+                    statement.visit(&local_sym, &local_env)?
+                }
+
+                Ok(())
+            },
+
+            Statement::Import { ref path, ref name, } => {
+                if sym.get_name(name).is_some() {
+                    return Err(ParserError::new(&format!("{}: already declared", name)))
+                }
+
+                let t = match sym.loader() {
+                    Some(loader) => loader.load(path)?,
+                    None         => return Err(ParserError::new(&format!("{}: can't import without a file to import from", path))),
+                };
+
+                declare(sym, env, name, t)
+            },
+
+            Statement::Export(ref exported) => exported.visit(sym, env),
+
+            // so uses of them don't fail as well
+            Statement::Error(ref names) => {
+                for name in names.iter() {
+                    if sym.get_name(name).is_none() {
+                        declare(sym, env, name, Type::Any)?
+                    }
+                }
+
+                Ok(())
+            },
+
+            Statement::Try { ref body, ref catch, ref finally, } => {
+                visit_scope(body, &[], &[], sym, env)?;
+
+                if let Some(ref catch) = *catch {
+                    // only errors of its type are caught, others go on
+                    if catch.t.complete(sym).lua_test("e").is_none() {
+                        return Err(ParserError::new(&format!("{}: can't tell errors of type '{:?}' apart", catch.name, catch.t)))
+                    }
+
+                    visit_scope(&catch.body, std::slice::from_ref(&catch.name), std::slice::from_ref(&catch.t), sym, env)?
+                }
+
+                if let Some(ref finally) = *finally {
+                    visit_scope(finally, &[], &[], sym, env)?
+                }
+
+                Ok(())
+            },
+
+            Statement::Class { ref name, ref t, ref methods, .. } => {
+                if sym.get_name(name).is_some() {
+                    return Err(ParserError::new(&format!("{}: already declared", name)))
+                }
+
+                declare(sym, env, name, self.get_type(sym, env)?)?;
+                sym.add_definition(name, t.clone());
+
+                for method in methods.iter() {
+                    method.visit_method(name, t, sym, env)?
+                }
+
+                Ok(())
+            },
+        }
+    }
+
+    // methods are funs taking the instance as self
+    fn visit_method(&self, owner: &Rc<String>, t: &Type, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<()> {
+        if let Statement::Fun { ref name, ref param_names, ref param_types, ref param_defaults, t: ref returns, ref body, } = *self {
+            let mut names = vec![Rc::new("self".to_string())];
+            names.extend(param_names.iter().cloned());
+
+            let mut types = vec![t.clone()];
+            types.extend(param_types.iter().cloned());
+
+            let mut defaults = vec![None];
+            defaults.extend(param_defaults.iter().cloned());
+
+            let fun = Expression::Fun {
+                param_names:    Rc::new(names),
+                param_types:    Rc::new(types),
+                param_defaults: Rc::new(defaults),
+                t:              returns.clone(),
+                body:           body.clone(),
+            };
+
+            if let Err(e) = fun.visit(sym, env) {
+                return Err(ParserError::new(&format!("{}.{}: {}", owner, name, e)))
+            }
+        }
+
+        Ok(())
+    }
+
+    // the type of a method on its owner, self is any as the owner isn't done yet
+    pub fn method_signature(&self) -> Option<(Rc<String>, Type)> {
+        match *self {
+            Statement::Fun { ref name, ref param_types, ref param_defaults, ref t, .. } => {
+                let mut types = vec![Type::Any];
+                types.extend(param_types.iter().cloned());
+
+                let mut defaults = vec![None];
+                defaults.extend(param_defaults.iter().cloned());
+
+                Some((name.clone(), Type::fun(t, &types, &defaults)))
+            },
+            _ => None,
+        }
+    }
+
+    // the names an exported statement adds to its module
+    pub fn exported_names(&self) -> Vec<Rc<String>> {
+        match *self {
+            Statement::Fun { ref name, .. } | Statement::Record { ref name, .. } | Statement::Enum { ref name, .. } | Statement::Class { ref name, .. } => vec![name.clone()],
+            Statement::Expression(ref e) => match **e {
+                Expression::Definition(_, ref target, _, _) => match **target {
+                    Expression::Identifier(ref name) => vec![name.clone()],
+                    _ => Vec::new(),
+                },
+                Expression::MultiDefinition(ref targets, _, _) => targets.iter().filter_map(|t| match *t {
+                    Expression::Identifier(ref name) => Some(name.clone()),
+                    _ => None,
+                }).collect(),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    // the record, enum or class declared, exported or not
+    pub fn type_name(&self) -> Option<Rc<String>> {
+        match *self {
+            Statement::Record { ref name, .. } | Statement::Enum { ref name, .. } | Statement::Class { ref name, .. } => Some(name.clone()),
+            Statement::Export(ref exported) => exported.type_name(),
+            _ => None,
+        }
+    }
+
+    // the named fun declared, exported or not
+    pub fn fun_name(&self) -> Option<Rc<String>> {
+        match *self {
+            Statement::Fun { ref name, .. } => Some(name.clone()),
+            Statement::Export(ref exported) => exported.fun_name(),
+            _ => None,
+        }
+    }
+
+    // the type of a fun's last statement, every value of a call when several are expected
+    pub fn returned_type(&self, expected: &Type, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+        match (self, expected) {
+            (Statement::Expression(e), &Type::Tuple(_)) => e.get_values_type(sym, env),
+            _ => self.get_type(sym, env),
+        }
+    }
+
+    fn is_return(&self) -> bool {
+        matches!(*self, Statement::Return(_))
+    }
+
+    fn always_returns(&self) -> bool {
+        match *self {
+            Statement::Return(_) => true,
+            Statement::Try { ref body, ref catch, .. } => {
+                let mut ends = vec![body.last()];
+
+                if let Some(ref catch) = *catch {
+                    ends.push(catch.body.last())
+                }
+
+                ends.iter().all(|s| s.is_some_and(|s| s.always_returns()))
+            },
+            _ => false,
+        }
+    }
+
+    pub fn get_type(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+        match *self {
+            Statement::Expression(ref e) => e.get_type(sym, env),
+            Statement::Fun { ref t, ref param_types, ref param_defaults, .. } => Ok(Type::fun(t, param_types, param_defaults)),
+            Statement::Record { ref name, ref fields, ref ops, } => {
+                let mut tp = vec![Type::Record(name.clone(), fields.clone(), Rc::new(ops.iter().filter_map(|o| o.method_signature()).collect()))];
+                tp.extend(fields.iter().map(|(_, t)| t.clone()));
+
+                Ok(Type::Fun(Rc::new(tp), fields.len()))
+            },
+            // the enum's name holds its constructors
+            Statement::Enum { ref name, ref variants, } => {
+                let t = Rc::new(Type::Enum(name.clone(), variants.clone()));
+
+                let constructors = variants.iter().map(|(variant, fields)| {
+                    let value = Type::Variant(t.clone(), variant.clone());
+
+                    if fields.is_empty() {
+                        return (variant.clone(), value)
+                    }
+
+                    let mut tp = vec![value];
+                    tp.extend(fields.iter().map(|(_, t)| t.clone()));
+
+                    (variant.clone(), Type::Fun(Rc::new(tp), fields.len()))
+                }).collect();
+
+                Ok(Type::Record(name.clone(), Rc::new(constructors), Rc::new(Vec::new())))
+            },
+            Statement::Interface { .. } | Statement::Alias { .. } => Ok(Type::Undefined),
+            Statement::Return(ref values) => Ok(Type::tuple(Expression::list_types(values, sym, env)?)),
+            Statement::For { .. } => Ok(Type::Nil),
+            Statement::Import { .. } => Ok(Type::Undefined),
+            Statement::Export(ref exported) => exported.get_type(sym, env),
+            Statement::Error(_) => Ok(Type::Any),
+            // only a value when every way out of it returns, and those are checked
+            Statement::Try { .. } => match env.returns() {
+                Some(t) if self.always_returns() => Ok(t),
+                _ => Ok(Type::Nil),
+            },
+            // the class table, with its constructor and methods
+            Statement::Class { ref name, ref t, ref methods, ref base, .. } => {
+                let mut statics = vec![(Rc::new("new".to_string()), class_constructor(t, methods, base))];
+
+                if let Type::Class(_, _, ref methods, _) = *t {
+                    statics.extend(methods.iter().cloned())
+                }
+
+                Ok(Type::Record(name.clone(), Rc::new(statics), Rc::new(Vec::new())))
+            },
+        }
+    }
+
+    pub fn lua(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Statement::Expression(ref e) => match **e {
+                Expression::Definition(_, _, _, _) |
+                Expression::Compound(_, _, _)   |
+                Expression::MultiDefinition(_, _, _) |
+                Expression::Destructure { .. }  => write!(f, "{}", e),
+                Expression::If(ref branches, ref otherwise) => lua_if(f, branches, otherwise, false),
+                Expression::Match(ref value, ref arms) => {
+                    let tmp = temp_name("match");
+
+                    writeln!(f, "do")?;
+                    writeln!(f, "local {} = {}", tmp, value)?;
+                    lua_arms(f, &tmp, arms, false)?;
+                    writeln!(f, "end")
+                },
+                _                               => writeln!(f, "{}", e),
+            },
+            Statement::Fun {
+                ref name, ref param_names, ref param_types, ref param_defaults, ref body, ref t,
+            } => {
+                lua_fun(f, Some(name), param_names, param_types, param_defaults, t, body)?;
+                writeln!(f)
+            },
+            Statement::Record { ref name, ref fields, ref ops, } => {
+                let names: Vec<&Rc<String>> = fields.iter().map(|(n, _)| n).collect();
+                let table: Vec<String>      = names.iter().map(|n| format!("{} = {}", n, n)).collect();
+
+                if ops.is_empty() {
+                    writeln!(f, "function {}({})", name, Expression::lua_list(&names))?;
+                    writeln!(f, "return {{{}}}", table.join(", "))?;
+                    return writeln!(f, "end")
+                }
+
+                // the operators are the metatable of every instance
+                let meta = temp_name("ops");
+
+                writeln!(f, "local {} = {{}}", meta)?;
+
+                for op in ops.iter() {
+                    if let Statement::Fun { name: ref op, ref param_names, ref param_types, ref param_defaults, ref t, ref body, } = *op {
+                        lua_method(f, &format!("{}.{}", meta, op), param_names, param_types, param_defaults, t, body)?;
+                        writeln!(f)?
+                    }
+                }
+
+                writeln!(f, "function {}({})", name, Expression::lua_list(&names))?;
+                writeln!(f, "return setmetatable({{{}}}, {})", table.join(", "), meta)?;
+                writeln!(f, "end")
+            },
+
+            Statement::Enum { ref name, ref variants, } => {
+                // fieldless enums are just numbered
+                if variants.iter().all(|(_, fields)| fields.is_empty()) {
+                    let numbered: Vec<String> = variants.iter().enumerate().map(|(i, (v, _))| format!("{} = {}", v, i + 1)).collect();
+
+                    return writeln!(f, "{} = {{{}}}", name, numbered.join(", "))
+                }
+
+                writeln!(f, "{} = {{", name)?;
+
+                for (variant, fields) in variants.iter() {
+                    let names: Vec<&Rc<String>> = fields.iter().map(|(n, _)| n).collect();
+
+                    let mut table = vec![format!("tag = \"{}\"", variant)];
+                    table.extend(names.iter().map(|n| format!("{} = {}", n, n)));
+
+                    if fields.is_empty() {
+                        writeln!(f, "{} = {{{}}},", variant, table.join(", "))?
+                    } else {
+                        writeln!(f, "{} = function({}) return {{{}}} end,", variant, Expression::lua_list(&names), table.join(", "))?
+                    }
+                }
+
+                writeln!(f, "}}")
+            },
+
+            Statement::Interface { .. } | Statement::Alias { .. } => Ok(()),
+
+            Statement::Return(ref values) => {
+                // out of a try body, saying it returned
+                if IN_TRY.with(|t| t.get()) {
+                    match values.len() {
+                        0 => writeln!(f, "return true"),
+                        _ => writeln!(f, "return true, {}", Expression::lua_list(values)),
+                    }
+                } else {
+                    writeln!(f, "return {}", Expression::lua_list(values))
+                }
+            },
+
+            // results are packed, a return inside keeps its trailing nils
+            Statement::Try { ref body, ref catch, ref finally, } => {
+                let tmp  = temp_name("try");
+                let pack = "(function(...) return {n = select(\"#\", ...), ...} end)";
+
+                writeln!(f, "do")?;
+
+                writeln!(f, "local {} = {}(pcall(function()", tmp, pack)?;
+                lua_try(f, body)?;
+                writeln!(f, "end))")?;
+
+                if let Some(ref catch) = *catch {
+                    match catch.t {
+                        Type::Any => writeln!(f, "if not {}[1] then", tmp)?,
+                        ref t     => writeln!(f, "if not {}[1] and {} then", tmp, t.lua_test(&format!("{}[2]", tmp)).unwrap_or_else(|| "true".to_string()))?,
+                    }
+
+                    writeln!(f, "{} = {}(pcall(function({})", tmp, pack, catch.name)?;
+                    lua_try(f, &catch.body)?;
+                    writeln!(f, "end, {}[2]))", tmp)?;
+                    writeln!(f, "end")?;
+                }
+
+                if let Some(ref finally) = *finally {
+                    lua_block(f, finally, false)?
+                }
+
+                writeln!(f, "if not {}[1] then error({}[2], 0) end", tmp, tmp)?;
+
+                if IN_TRY.with(|t| t.get()) {
+                    writeln!(f, "if {}[2] then return true, (table.unpack or unpack)({}, 3, {}.n) end", tmp, tmp, tmp)?;
+                } else {
+                    writeln!(f, "if {}[2] then return (table.unpack or unpack)({}, 3, {}.n) end", tmp, tmp, tmp)?;
+                }
+
+                writeln!(f, "end")
+            },
+
+            Statement::For { ref names, ref iterable, ref body, ref over, } => {
+                match over.get() {
+                    Iteration::Map                       => writeln!(f, "for {} in pairs({}) do", Expression::lua_list(names), iterable)?,
+                    Iteration::Iter                      => writeln!(f, "for {} in {} do", names[0], iterable)?,
+                    Iteration::Array if names.len() == 1 => writeln!(f, "for _, {} in ipairs({}) do", names[0], iterable)?,
+                    Iteration::Array                     => writeln!(f, "for {} in ipairs({}) do", Expression::lua_list(names), iterable)?,
+                }
+
+                lua_block(f, body, false)?;
+                writeln!(f, "end")
+            },
+
+            Statement::Import { ref path, ref name, } => writeln!(f, "local {} = require(\"{}\")", name, path),
+
+            Statement::Export(ref exported) => exported.lua(f),
+
+            Statement::Error(_) => Ok(()),
+
+            Statement::Class { ref name, ref parent, ref t, ref methods, ref base, } => {
+                match *parent {
+                    Some(ref parent) => writeln!(f, "{} = setmetatable({{}}, {{__index = {}}})", name, parent)?,
+                    None             => writeln!(f, "{} = {{}}", name)?,
+                }
+
+                let own: Vec<&Rc<String>> = methods.iter().filter_map(|m| match *m {
+                    Statement::Fun { ref name, .. } => Some(name),
+                    _ => None,
+                }).collect();
+
+                let inherited: Vec<&Rc<String>> = match *t {
+                    Type::Class(_, _, ref methods, _) => methods.iter().map(|(n, _)| n).filter(|n| n.starts_with("__") && !own.contains(n)).collect(),
+                    _ => Vec::new(),
+                };
+
+                // lua doesn't look up metamethods through __index
+                if let Some(ref parent) = *parent {
+                    for op in inherited.iter().filter(|n| n.as_str() != "__index") {
+                        writeln!(f, "{}.{} = {}.{}", name, op, parent, op)?
+                    }
+                }
+
+                // a user [] gets what the class doesn't have
+                if own.iter().chain(inherited.iter()).any(|n| n.as_str() == "__index") {
+                    writeln!(f, "{}.__index = function(self, key)", name)?;
+                    writeln!(f, "local value = {}[key]", name)?;
+                    writeln!(f, "if value == nil then return {}.__get(self, key) end", name)?;
+                    writeln!(f, "return value")?;
+                    writeln!(f, "end")?;
+                } else {
+                    writeln!(f, "{}.__index = {}", name, name)?;
+                }
+
+                let constructor = methods.iter().find(|m| match **m {
+                    Statement::Fun { name: ref m, .. } => m.as_str() == "new",
+                    _ => false,
+                });
+
+                // new makes the instance, init sets it up and is what super calls
+                let params = match constructor {
+                    Some(Statement::Fun { param_names, param_types, .. }) => lua_params(param_names, param_types),
+                    _ => base.iter().chain(own_fields(t).iter()).map(|(n, _)| n.to_string()).collect(),
+                };
+
+                match constructor {
+                    Some(Statement::Fun { param_names, param_types, param_defaults, body, .. }) => {
+                        lua_method(f, &format!("{}.init", name), param_names, param_types, param_defaults, &None, body)?;
+                        writeln!(f)?
+                    },
+                    _ => {
+                        let mut with_self = vec!["self".to_string()];
+                        with_self.extend(params.iter().cloned());
+
+                        writeln!(f, "function {}.init({})", name, with_self.join(","))?;
+
+                        // the parent sets up what it has, with what its new takes
+                        if let Some(ref parent) = *parent {
+                            let mut passed = vec!["self".to_string()];
+                            passed.extend(base.iter().map(|(n, _)| n.to_string()));
+
+                            writeln!(f, "{}.init({})", parent, passed.join(","))?
+                        }
+
+                        for (field, _) in own_fields(t).iter() {
+                            writeln!(f, "self.{} = {}", field, field)?
+                        }
+
+                        writeln!(f, "end")?
+                    },
+                }
+
+                let mut with_self = vec!["self".to_string()];
+                with_self.extend(params.iter().cloned());
+
+                writeln!(f, "function {}.new({})", name, params.join(","))?;
+                writeln!(f, "local self = setmetatable({{}}, {})", name)?;
+                writeln!(f, "{}.init({})", name, with_self.join(","))?;
+                writeln!(f, "return self")?;
+                writeln!(f, "end")?;
+
+                for method in methods.iter() {
+                    if let Statement::Fun { name: ref method, ref param_names, ref param_types, ref param_defaults, ref t, ref body, } = *method {
+                        let method = match method.as_str() {
+                            "new"     => continue,
+                            "__index" => "__get",
+                            method    => method,
+                        };
+
+                        lua_method(f, &format!("{}.{}", name, method), param_names, param_types, param_defaults, t, body)?;
+                        writeln!(f)?
+                    }
+                }
+
+                Ok(())
+            },
+        }
+    }
+}
+
+// the fields a class declares itself, after the ones it inherits
+pub fn own_fields(t: &Type) -> Vec<(Rc<String>, Type)> {
+    match *t {
+        Type::Class(_, ref fields, _, ref parent) => {
+            let inherited = match parent.as_deref() {
+                Some(Type::Class(_, ref fields, _, _)) => fields.len(),
+                _ => 0,
+            };
+
+            fields[inherited ..].to_vec()
+        },
+        _ => Vec::new(),
+    }
+}
+
+// what Class.new takes, without a constructor what the parent's takes and then the class's own fields
+fn class_constructor(t: &Type, methods: &[Statement], base: &Fields) -> Type {
+    let constructor = methods.iter().find(|m| match **m {
+        Statement::Fun { ref name, .. } => name.as_str() == "new",
+        _ => false,
+    });
+
+    match (constructor, t) {
+        (Some(Statement::Fun { param_types, param_defaults, .. }), _) => Type::fun(&Some(t.clone()), param_types, param_defaults),
+        (_, Type::Class(..)) => {
+            let params: Vec<Type> = base.iter().cloned().chain(own_fields(t)).map(|(_, t)| t).collect();
+
+            let mut tp = vec![t.clone()];
+            tp.extend(params.iter().cloned());
+
+            Type::Fun(Rc::new(tp), params.len())
+        },
+        _ => Type::Any,
+    }
+}
+
+// a fun's params in lua, where variadic ones are ...
+fn lua_params(param_names: &[Rc<String>], param_types: &[Type]) -> Vec<String> {
+    let mut params: Vec<String> = param_names.iter().map(|p| p.to_string()).collect();
+
+    if let Some(&Type::Many(_)) = param_types.last() {
+        params.pop();
+        params.push("...".to_string())
+    }
+
+    params
+}
+
+fn lua_method(f: &mut fmt::Formatter, name: &str, param_names: &[Rc<String>], param_types: &[Type], param_defaults: &[Option<Rc<Expression>>], t: &Option<Type>, body: &[Statement]) -> fmt::Result {
+    let mut names = vec![Rc::new("self".to_string())];
+    names.extend(param_names.iter().cloned());
+
+    let mut types = vec![Type::Any];
+    types.extend(param_types.iter().cloned());
+
+    let mut defaults = vec![None];
+    defaults.extend(param_defaults.iter().cloned());
+
+    lua_fun(f, Some(&Rc::new(name.to_string())), &names, &types, &defaults, t, body)
+}
+
+fn check_args(id: &Expression, params: &[Type], required: usize, arg_types: &[Option<Type>]) -> ParserResult<()> {
+    let (fixed, many) = match params.last() {
+        Some(Type::Many(t)) => (&params[1 .. params.len() - 1], Some(&**t)),
+        _                        => (&params[1 ..], None),
+    };
+
+    if arg_types.len() < required {
+        return Err(ParserError::new(&format!("{}: expected at least {} args, got {}", id, required, arg_types.len())))
+    }
+
+    if many.is_none() && arg_types.len() > fixed.len() {
+        return Err(ParserError::new(&format!("{}: expected at most {} args, got {}", id, fixed.len(), arg_types.len())))
+    }
+
+    for (i, arg_t) in arg_types.iter().enumerate() {
+        let t = fixed.get(i).or(many).unwrap();
+
+        match *arg_t {
+            Some(ref arg_t) => if !t.compare(arg_t) {
+                return Err(ParserError::new(&format!("{}: expected '{:?}' got '{:?}'", id, t, arg_t)))
+            },
+            None => if i < required {
+                return Err(ParserError::new(&format!("{}: missing arg {}", id, i + 1)))
+            },
+        }
+    }
+
+    Ok(())
+}
+
+// the type params in e's type that are the funs around it's, where a declared fun's are its own
+fn fixed_vars(e: &Expression, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> Vec<Rc<String>> {
+    match *e {
+        Expression::Identifier(ref name) if sym.get_params(name).is_some() => Vec::new(),
+        _ => env.fixed(),
+    }
+}
+
+fn visit_defaults(sym: &Rc<SymTab>, env: &Rc<TypeTab>, param_names: &[Rc<String>], param_types: &[Type], param_defaults: &[Option<Rc<Expression>>]) -> ParserResult<()> {
+    for (i, default) in param_defaults.iter().enumerate() {
+        if let Some(ref e) = *default {
+            e.visit(sym, env)?;
+
+            let tp = e.get_type(sym, env)?;
+            if !param_types[i].compare(&tp) {
+                return Err(ParserError::new(&format!("{}: expected default '{:?}', got '{:?}'", param_names[i], param_types[i], tp)))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn lua_fun(f: &mut fmt::Formatter, name: Option<&Rc<String>>, param_names: &[Rc<String>], param_types: &[Type], param_defaults: &[Option<Rc<Expression>>], t: &Option<Type>, body: &[Statement]) -> fmt::Result {
+    match name {
+        Some(n) => write!(f, "function {}", n)?,
+        None    => write!(f, "function")?,
+    }
+
+    let mut params: Vec<String> = param_names.iter().map(|p| format!("{}", p)).collect();
+
+    let variadic = match param_types.last() {
+        Some(&Type::Many(_)) => params.pop(),
+        _                    => None,
+    };
+
+    if variadic.is_some() {
+        params.push("...".to_string())
+    }
+
+    writeln!(f, "({})", params.join(","))?;
+
+    if let Some(v) = variadic {
+        writeln!(f, "local {} = {{n = select(\"#\", ...), ...}}", v)?;
+    }
+
+    for (name, default) in param_names.iter().zip(param_defaults.iter()) {
+        if let Some(ref e) = *default {
+            writeln!(f, "if {} == nil then {} = {} end", name, name, e)?;
+        }
+    }
+
+    // returns in a fun are its own, even inside a try
+    let outer = IN_TRY.with(|t| t.replace(false));
+
+    // an async fun starts a task running its body
+    if is_async(t) {
+        use_async();
+
+        writeln!(f, "return __async.run(function()")?;
+        lua_body(f, body)?;
+        writeln!(f, "end)")?;
+    } else if yielded(t).is_some() {
+        writeln!(f, "return coroutine.wrap(function()")?;
+        lua_block(f, body, false)?;
+        writeln!(f, "end)")?;
+    } else {
+        lua_body(f, body)?;
+    }
+
+    IN_TRY.with(|t| t.set(outer));
+
+    write!(f, "end")
+}
+
+// the last expression is returned
+fn lua_body(f: &mut fmt::Formatter, body: &[Statement]) -> fmt::Result {
+    for (i, s) in body.iter().enumerate() {
+        if i == body.len() - 1 {
+            match *s {
+                Statement::Expression(ref e) => match **e {
+                    Expression::If(ref branches, ref otherwise) => lua_if(f, branches, otherwise, true)?,
+                    // assignments aren't values
+                    Expression::Definition(_, _, _, _) |
+                    Expression::Compound(_, _, _)   |
+                    Expression::MultiDefinition(_, _, _) |
+                    Expression::Destructure { .. }  => { write!(f, "{}", s)?; },
+                    _ => { writeln!(f, "return {}", e)?; },
+                },
+                _ => { write!(f, "{}", s)?; },
+            }
+        } else {
+            lua_statement(f, s, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+// a try body in the closure pcall calls
+fn lua_try(f: &mut fmt::Formatter, body: &[Statement]) -> fmt::Result {
+    let outer = IN_TRY.with(|t| t.replace(true));
+    lua_block(f, body, false)?;
+    IN_TRY.with(|t| t.set(outer));
+
+    Ok(())
+}
+
+// lua only allows a return at the end of a block
+fn lua_statement(f: &mut fmt::Formatter, s: &Statement, last: bool) -> fmt::Result {
+    if s.is_return() && !last {
+        write!(f, "do\n{}end\n", s)
+    } else {
+        write!(f, "{}", s)
+    }
+}
+
+fn lua_if(f: &mut fmt::Formatter, branches: &[Branch], otherwise: &Option<Rc<Vec<Statement>>>, returns: bool) -> fmt::Result {
+    for (i, branch) in branches.iter().enumerate() {
+        if i == 0 {
+            writeln!(f, "if {} then", branch.condition)?
+        } else {
+            writeln!(f, "elseif {} then", branch.condition)?
+        }
+
+        lua_block(f, &branch.body, returns)?
+    }
+
+    if let Some(ref body) = *otherwise {
+        writeln!(f, "else")?;
+        lua_block(f, body, returns)?
+    }
+
+    writeln!(f, "end")
+}
+
+fn lua_block(f: &mut fmt::Formatter, body: &[Statement], returns: bool) -> fmt::Result {
+    if returns {
+        return lua_body(f, body)
+    }
+
+    for (i, s) in body.iter().enumerate() {
+        lua_statement(f, s, i + 1 == body.len())?
+    }
+
+    Ok(())
+}
+
+pub fn is_async(t: &Option<Type>) -> bool {
+    matches!(*t, Some(Type::Async(_)))
+}
+
+// what a generator gives, undefined until its first yield
+pub fn yielded(t: &Option<Type>) -> Option<Type> {
+    match *t {
+        Some(Type::Iter(ref t)) => Some((**t).clone()),
+        _ => None,
+    }
+}
+
+// a match, where a statement may also return from its arms
+fn visit_match(value: &Expression, arms: &[Arm], sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<()> {
+    value.visit(sym, env)?;
+
+    let tp = value.get_type(sym, env)?;
+
+    let cases = tp.cases();
+
+    let mut covered = false;
+    let mut seen: Vec<String> = Vec::new();
+
+    for arm in arms.iter() {
+        let local_sym = Rc::new(SymTab::new(sym.clone(), &Vec::new()));
+        let local_env = Rc::new(TypeTab::new(env.clone(), &Vec::new()));
+
+        arm.pattern.bind(&local_sym, &local_env, &tp)?;
+
+        if let Some(ref guard) = arm.guard {
+            guard.visit(&local_sym, &local_env)?;
+
+            let t = guard.get_type(&local_sym, &local_env)?;
+            if !Type::Bool.compare(&t) {
+                return Err(ParserError::new(&format!("{}: expected 'Bool' guard, got '{:?}'", arm.pattern, t)))
+            }
+        }
+
+        for statement in arm.body.iter() {
+            statement.visit(&local_sym, &local_env)?
+        }
+
+        if covered {
+            sym.warn(&format!("unreachable match arm: {}", arm.pattern))
+        }
+
+        if arm.guard.is_some() {
+            continue
+        }
+
+        match arm.pattern.case() {
+            Some(case) => {
+                if !covered && seen.contains(&case) {
+                    sym.warn(&format!("unreachable match arm: {}", arm.pattern))
+                }
+
+                seen.push(case)
+            },
+            None => if arm.pattern.is_irrefutable() {
+                covered = true
+            },
+        }
+
+        if let Some(ref cases) = cases {
+            if cases.iter().all(|c| seen.contains(c)) {
+                covered = true
+            }
+        }
+    }
+
+    if let (false, Some(cases)) = (covered, cases) {
+        let missing: Vec<String> = cases.into_iter().filter(|c| !seen.contains(c)).collect();
+
+        sym.warn(&format!("non-exhaustive match on {}: missing {}", value, missing.join(", ")))
+    }
+
+    Ok(())
+}
+
+// a return somewhere in a block, past branches and loops but not funs
+fn returns_within(body: &[Statement]) -> bool {
+    body.iter().any(|s| match *s {
+        Statement::Return(_) => true,
+        Statement::Expression(ref e) => match **e {
+            Expression::If(ref branches, ref otherwise) => {
+                branches.iter().any(|b| returns_within(&b.body)) || otherwise.as_ref().is_some_and(|o| returns_within(o))
+            },
+            Expression::Match(_, ref arms) => arms.iter().any(|a| returns_within(&a.body)),
+            _ => false,
+        },
+        Statement::For { ref body, .. } => returns_within(body),
+        Statement::Try { ref body, ref catch, ref finally, } => {
+            returns_within(body) || catch.as_ref().is_some_and(|c| returns_within(&c.body)) || finally.as_ref().is_some_and(|b| returns_within(b))
+        },
+        _ => false,
+    })
+}
+
+// a block with its own names
+fn visit_scope(body: &[Statement], names: &[Rc<String>], types: &[Type], sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<()> {
+    let local_sym = Rc::new(SymTab::new(sym.clone(), names));
+    let local_env = Rc::new(TypeTab::new(env.clone(), types));
+
+    for statement in body.iter() {
+        statement.visit(&local_sym, &local_env)?
+    }
+
+    Ok(())
+}
+
+// a scope where narrowed names shadow their optional selves
+fn narrowed(sym: &Rc<SymTab>, env: &Rc<TypeTab>, narrowings: &[(Rc<String>, Type)]) -> ParserResult<(Rc<SymTab>, Rc<TypeTab>)> {
+    let local_sym = Rc::new(SymTab::new_narrowing(sym.clone()));
+    let local_env = Rc::new(TypeTab::new(env.clone(), &Vec::new()));
+
+    for (name, t) in narrowings.iter() {
+        declare(&local_sym, &local_env, name, t.clone())?
+    }
+
+    Ok((local_sym, local_env))
+}
+
+// an if/elseif chain over the matched value, stored in `tmp`
+fn lua_arms(f: &mut fmt::Formatter, tmp: &str, arms: &[Arm], returns: bool) -> fmt::Result {
+    for (i, arm) in arms.iter().enumerate() {
+        let mut tests    = Vec::new();
+        let mut bindings = Vec::new();
+
+        arm.pattern.lua_test(tmp, &mut tests, &mut bindings);
+
+        let names: Vec<&str> = bindings.iter().map(|(n, _)| n.as_str()).collect();
+        let paths: Vec<&str> = bindings.iter().map(|(_, p)| p.as_str()).collect();
+
+        if let Some(ref guard) = arm.guard {
+            if bindings.is_empty() {
+                tests.push(format!("({})", guard))
+            } else {
+                tests.push(format!("(function({}) return {} end)({})", names.join(", "), guard, paths.join(", ")))
+            }
+        }
+
+        if i == 0 {
+            writeln!(f, "if {} then", if tests.is_empty() { "true".to_string() } else { tests.join(" and ") })?
+        } else if tests.is_empty() {
+            writeln!(f, "else")?
+        } else {
+            writeln!(f, "elseif {} then", tests.join(" and "))?
+        }
+
+        if !bindings.is_empty() {
+            writeln!(f, "local {} = {}", names.join(", "), paths.join(", "))?
+        }
+
+        lua_block(f, &arm.body, returns)?;
+
+        // nothing gets past an arm that matches anything, the checker warns about the rest
+        if tests.is_empty() {
+            break
+        }
+    }
+
+    if !arms.is_empty() {
+        writeln!(f, "end")?
+    }
+
+    Ok(())
+}
+
+// adds a name to the innermost scope, shadowing outer ones
+fn declare(sym: &Rc<SymTab>, env: &Rc<TypeTab>, name: &str, tp: Type) -> ParserResult<()> {
+    let index = sym.add_name(name);
+    if index >= env.size() {
+        env.grow();
+    }
+
+    if let Err(e) = env.set_type(index, 0, tp) {
+        Err(ParserError::new(&format!("error setting type: {}", e)))
+    } else {
+        Ok(())
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.lua(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard,
+    Literal(Expression),
+    Binding(Rc<String>),
+    Array(Rc<Vec<Pattern>>),
+    Table(Rc<Vec<(Rc<String>, Pattern)>>),
+    Variant(Type, Rc<String>, Rc<Vec<Pattern>>), // the enum, the variant and its fields in order
+}
+
+impl Pattern {
+    // declares the names bound by the pattern when matching a value of type `tp`
+    pub fn bind(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>, tp: &Type) -> ParserResult<()> {
+        match *self {
+            Pattern::Wildcard => Ok(()),
+
+            Pattern::Literal(ref e) => {
+                let t = e.get_type(sym, env)?;
+                if !tp.compare(&t) {
+                    return Err(ParserError::new(&format!("{}: can't match '{:?}' against '{:?}'", self, t, tp)))
+                }
+
+                Ok(())
+            },
+
+            Pattern::Binding(ref name) => declare(sym, env, name, tp.clone()),
+
+            Pattern::Array(ref content) => {
+                let t = match *tp {
+                    Type::Array(ref t) => (**t).clone(),
+                    Type::Any          => Type::Any,
+                    ref t              => return Err(ParserError::new(&format!("{}: can't match array against '{:?}'", self, t))),
+                };
+
+                for p in content.iter() {
+                    p.bind(sym, env, &t)?
+                }
+
+                Ok(())
+            },
+
+            Pattern::Variant(ref t, ref name, ref content) => {
+                let matches = match *tp {
+                    Type::Any               => true,
+                    Type::Variant(ref e, _) => **e == *t,
+                    ref tp                  => tp == t,
+                };
+
+                if !matches {
+                    return Err(ParserError::new(&format!("{}: can't match variant against '{:?}'", self, tp)))
+                }
+
+                let fields = t.fields(name);
+
+                if fields.len() != content.len() {
+                    return Err(ParserError::new(&format!("{}: expected {} fields, got {}", self, fields.len(), content.len())))
+                }
+
+                for ((_, t), p) in fields.iter().zip(content.iter()) {
+                    p.bind(sym, env, t)?
+                }
+
+                Ok(())
+            },
+
+            Pattern::Table(ref content) => {
+                for (key, p) in content.iter() {
+                    let t = match *tp {
+                        Type::Any => Type::Any,
+                        Type::Record(ref record, ref fields, _) | Type::Interface(ref record, ref fields) | Type::Class(ref record, ref fields, _, _) => match fields.iter().find(|&(n, _)| n == key) {
+                            Some((_, t)) => t.clone(),
+                            None              => return Err(ParserError::new(&format!("{}: no field '{}' in '{}'", self, key, record))),
+                        },
+                        ref t => return Err(ParserError::new(&format!("{}: can't match table against '{:?}'", self, t))),
+                    };
+
+                    p.bind(sym, env, &t)?
+                }
+
+                Ok(())
+            },
+        }
+    }
+
+    // matches anything
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(*self, Pattern::Wildcard | Pattern::Binding(_))
+    }
+
+    // the single case of a finite type this pattern covers entirely
+    pub fn case(&self) -> Option<String> {
+        match *self {
+            Pattern::Literal(ref e) => Some(format!("{}", e)),
+            Pattern::Variant(_, ref name, ref content) if content.iter().all(|p| p.is_irrefutable()) => Some(format!("{}", name)),
+            _ => None,
+        }
+    }
+
+    // collects the conditions on the value at `path`, and where each binding lives
+    pub fn lua_test(&self, path: &str, tests: &mut Vec<String>, bindings: &mut Vec<(String, String)>) {
+        match *self {
+            Pattern::Wildcard          => (),
+            Pattern::Literal(ref e)    => tests.push(format!("{} == {}", path, e)),
+            Pattern::Binding(ref name) => bindings.push((format!("{}", name), path.to_string())),
+
+            Pattern::Array(ref content) => {
+                tests.push(format!("type({}) == \"table\" and #{} == {}", path, path, content.len()));
+
+                for (i, p) in content.iter().enumerate() {
+                    p.lua_test(&format!("{}[{}]", path, i + 1), tests, bindings)
+                }
+            },
+
+            Pattern::Variant(ref t, ref name, ref content) => {
+                if let Type::Enum(_, ref variants) = *t {
+                    if variants.iter().all(|(_, fields)| fields.is_empty()) {
+                        let i = variants.iter().position(|(v, _)| v == name).unwrap();
+
+                        return tests.push(format!("{} == {}", path, i + 1))
+                    }
+                }
+
+                tests.push(format!("{}.tag == \"{}\"", path, name));
+
+                for ((field, _), p) in t.fields(name).iter().zip(content.iter()) {
+                    p.lua_test(&format!("{}.{}", path, field), tests, bindings)
+                }
+            },
+
+            Pattern::Table(ref content) => {
+                tests.push(format!("type({}) == \"table\"", path));
+
+                for (key, p) in content.iter() {
+                    p.lua_test(&format!("{}.{}", path, key), tests, bindings)
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Pattern::Wildcard          => write!(f, "_"),
+            Pattern::Literal(ref e)    => write!(f, "{}", e),
+            Pattern::Binding(ref name) => write!(f, "{}", name),
+            Pattern::Array(ref content) => {
+                write!(f, "[{}]", Expression::lua_list(content))
+            },
+            Pattern::Variant(_, ref name, ref content) => if content.is_empty() {
+                write!(f, "{}", name)
+            } else {
+                write!(f, "{}({})", name, Expression::lua_list(content))
+            },
+            Pattern::Table(ref content) => {
+                let fields: Vec<String> = content.iter().map(|(k, p)| format!("{}: {}", k, p)).collect();
+                write!(f, "[{}]", fields.join(", "))
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    pub condition: Rc<Expression>,
+    pub body:      Rc<Vec<Statement>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catch {
+    pub name: Rc<String>,
+    pub t:    Type, // any, unless the error is declared
+    pub body: Rc<Vec<Statement>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard:   Option<Rc<Expression>>,
+    pub body:    Rc<Vec<Statement>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Str, Num, Bool, Any, Nil, Array(Rc<Type>), Undefined,
+    Fun(Rc<Vec<Type>>, usize), Many(Rc<Type>), // return type first, then params; number of required params
+    Record(Rc<String>, Fields, Fields), // fields, then operators by metamethod
+    Enum(Rc<String>, Rc<Vec<(Rc<String>, Fields)>>),
+    Variant(Rc<Type>, Rc<String>), // a value known to be one variant of an enum
+    Interface(Rc<String>, Fields),
+    Var(Rc<String>), // a type param, bound per call
+    Optional(Rc<Type>), // the type or nil
+    Union(Rc<Vec<Type>>), // normalized by Type::union
+    Tuple(Rc<Vec<Type>>), // several values, only returned by funs
+    Map(Rc<Type>, Rc<Type>), // keys, then values
+    Async(Rc<Type>), // the task an async fun starts, awaiting it gives the type
+    Iter(Rc<Type>), // what funs that yield return, for loops go through it
+    Class(Rc<String>, Fields, Fields, Option<Rc<Type>>), // fields, methods taking self and the parent, both with the parent's
+}
+
+// named fields of records and enum variants
+pub type Fields = Rc<Vec<(Rc<String>, Type)>>;
+
+#[allow(unused)]
+impl Type {
+    pub fn fun(t: &Option<Type>, param_types: &[Type], param_defaults: &[Option<Rc<Expression>>]) -> Type {
+        let mut tp = Vec::new();
+
+        if let &Some(Type::Iter(ref e)) = t {
+            // not inferred yet
+            tp.push(Type::Iter(Rc::new(match **e {
+                Type::Undefined => Type::Any,
+                ref e           => e.clone(),
+            })))
+        } else if let Some(t) = t {
+            tp.push(t.clone())
+        } else {
+            tp.push(Type::Any)
+        }
+
+        for t in param_types.iter() {
+            tp.push(t.clone())
+        }
+
+        let required = param_types.iter().zip(param_defaults.iter()).filter(|&(t, d)| match *t {
+            Type::Many(_) => false,
+            _             => d.is_none(),
+        }).count();
+
+        Type::Fun(Rc::new(tp), required)
+    }
+
+    // the type a new name gets from its first value
+    pub fn widen(&self) -> Type {
+        match *self {
+            Type::Variant(ref e, _) => (**e).clone(),
+            ref t                   => t.clone(),
+        }
+    }
 
-                let mut acc = 1;
-                for e in args.iter() {
-                    write!(f, "{}", e)?;
-                    if acc != args.len() {
-                        write!(f, ",")?;
-                    }
-                    acc += 1;
-                }
+    pub fn is_generic(&self) -> bool {
+        !self.vars().is_empty()
+    }
 
-                write!(f, ")")
-            },
+    // the type params a type uses
+    pub fn vars(&self) -> Vec<Rc<String>> {
+        let inner: Vec<&Type> = match *self {
+            Type::Var(ref name) => return vec![name.clone()],
+            Type::Array(ref t) | Type::Many(ref t) | Type::Optional(ref t) => vec![t],
+            Type::Map(ref k, ref v) => vec![k, v],
+            Type::Fun(ref types, _) | Type::Union(ref types) | Type::Tuple(ref types) => types.iter().collect(),
+            Type::Record(_, ref fields, _) | Type::Interface(_, ref fields) => fields.iter().map(|(_, t)| t).collect(),
+            _ => Vec::new(),
+        };
+
+        let mut vars = Vec::new();
+
+        for v in inner.iter().flat_map(|t| t.vars()) {
+            if !vars.contains(&v) {
+                vars.push(v)
+            }
+        }
 
-            Expression::Fun {
-                ref t, ref param_names, ref param_types, ref body,
-            } => {
-                write!(f, "function")?;
+        vars
+    }
 
-                write!(f, "(")?;
+    // renames the type params not fixed by the funs around, so one call's don't clash with another's
+    fn fresh(&self, call: usize, fixed: &[Rc<String>]) -> Type {
+        let bindings = self.vars().iter().filter(|v| !fixed.contains(v)).map(|v| (v.to_string(), Type::Var(Rc::new(format!("{}'{}", v, call))))).collect();
 
-                for e in param_names.iter() {
-                    write!(f, "{}", e)?;
-                    if e != param_names.last().unwrap() {
-                        write!(f, ",")?;
-                    }
-                }
-                
-                writeln!(f, ")")?;
-                
-                for s in body.iter() {
-                    if s == body.last().unwrap() {
-                        match *s {
-                            Statement::Expression(ref e) => { write!(f, "return {}\n", e)?; },
-                            _ => { write!(f, "{}", s)?; },
-                        }
-                    } else {
-                        write!(f, "{}", s)?;
+        self.substitute(&bindings)
+    }
+
+    // renamed by fresh, so bound per call rather than fixed by an enclosing generic
+    fn is_flexible(name: &str) -> bool {
+        name.contains('\'')
+    }
+
+    // replaces type params, following bindings to other params; unbound ones stay
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        let fields = |fields: &Fields| Rc::new(fields.iter().map(|(n, t)| (n.clone(), t.substitute(bindings))).collect());
+
+        match *self {
+            Type::Var(ref name)                => bindings.get(name.as_str()).map(|t| t.substitute(bindings)).unwrap_or_else(|| self.clone()),
+            Type::Array(ref t)                 => Type::Array(Rc::new(t.substitute(bindings))),
+            Type::Many(ref t)                  => Type::Many(Rc::new(t.substitute(bindings))),
+            Type::Optional(ref t)              => t.substitute(bindings).optional(),
+            Type::Union(ref types)             => Type::union(types.iter().map(|t| t.substitute(bindings)).collect()),
+            Type::Tuple(ref types)             => Type::Tuple(Rc::new(types.iter().map(|t| t.substitute(bindings)).collect())),
+            Type::Map(ref k, ref v)            => Type::Map(Rc::new(k.substitute(bindings)), Rc::new(v.substitute(bindings))),
+            Type::Fun(ref types, required)     => Type::Fun(Rc::new(types.iter().map(|t| t.substitute(bindings)).collect()), required),
+            Type::Record(ref name, ref f, ref o) => Type::Record(name.clone(), fields(f), fields(o)),
+            Type::Interface(ref name, ref f)   => Type::Interface(name.clone(), fields(f)),
+            ref t                              => t.clone(),
+        }
+    }
+
+    // binds the fresh type params on either side to the matching parts of the other,
+    // params only ever met by any or nil are noted in loose
+    fn unify(&self, other: &Type, bindings: &mut HashMap<String, Type>, loose: &mut Vec<String>) {
+        match (self, other) {
+            (Type::Var(name), _) if bindings.contains_key(name.as_str()) => bindings[name.as_str()].clone().unify(other, bindings, loose),
+            (_, Type::Var(name)) if bindings.contains_key(name.as_str()) => self.unify(&bindings[name.as_str()].clone(), bindings, loose),
+
+            (Type::Var(a), Type::Var(b)) if a == b => (),
+
+            (Type::Var(name), t) | (t, Type::Var(name)) if Type::is_flexible(name) => match *t {
+                Type::Any | Type::Nil | Type::Undefined => loose.push(name.to_string()),
+                _ => {
+                    let t = t.substitute(bindings).widen();
+
+                    // binding a param to something holding it would never end
+                    if !t.vars().contains(name) {
+                        bindings.insert(name.to_string(), t);
                     }
-                }
-                
-                write!(f, "end")
+                },
             },
-            
-            Expression::Operation {
-                ref left, ref op, ref right,
-            } => {
-                write!(f, "{}", left)?;
-                write!(f, " {} ", op)?;
-                write!(f, "{}", right)
+
+            (Type::Array(a), Type::Array(b)) => a.unify(b, bindings, loose),
+
+            (Type::Map(a, v), Type::Map(b, u)) => {
+                a.unify(b, bindings, loose);
+                v.unify(u, bindings, loose)
             },
 
-            _ => Ok(()),
+            (Type::Optional(a), Type::Optional(b)) => a.unify(b, bindings, loose),
+            (Type::Optional(a), b)                 => a.unify(b, bindings, loose),
+
+            (&Type::Fun(ref a, _), &Type::Fun(ref b, _)) | (&Type::Tuple(ref a), &Type::Tuple(ref b)) => for (a, b) in a.iter().zip(b.iter()) {
+                a.unify(b, bindings, loose)
+            },
+
+            (Type::Record(a_name, a, _), Type::Record(b_name, b, _)) if a_name == b_name => for ((_, a), (_, b)) in a.iter().zip(b.iter()) {
+                a.unify(b, bindings, loose)
+            },
+
+            _ => (),
         }
     }
-}
 
-impl fmt::Display for Expression {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.lua(f)
+    // a fun's params and return type, and the arg types, for one call; a generic fun
+    // passed as an arg gets its type params bound along with the callee's. fixed holds
+    // the type params the callee, then each arg, takes from the funs around
+    pub fn instantiate(id: &Expression, params: &[Type], arg_types: &[Option<Type>], fixed: &[Vec<Rc<String>>]) -> ParserResult<(Vec<Type>, Vec<Option<Type>>)> {
+        if !params.iter().chain(arg_types.iter().flatten()).any(|t| t.is_generic()) {
+            return Ok((params.to_vec(), arg_types.to_vec()))
+        }
+
+        let params: Vec<Type> = params.iter().map(|p| p.fresh(0, &fixed[0])).collect();
+        let arg_types: Vec<Option<Type>> = arg_types.iter().enumerate().map(|(i, t)| t.as_ref().map(|t| t.fresh(i + 1, &fixed[i + 1]))).collect();
+
+        let (fixed, many) = match params.last() {
+            Some(Type::Many(t)) => (&params[1 .. params.len() - 1], Some(&**t)),
+            _                        => (&params[1 ..], None),
+        };
+
+        let mut bindings = HashMap::new();
+        let mut loose    = Vec::new();
+
+        for (i, arg) in arg_types.iter().enumerate() {
+            if let (Some(p), Some(arg)) = (fixed.get(i).or(many), arg) {
+                p.unify(arg, &mut bindings, &mut loose)
+            }
+        }
+
+        for name in loose {
+            bindings.entry(name).or_insert(Type::Any);
+        }
+
+        let params: Vec<Type> = params.iter().map(|p| p.substitute(&bindings)).collect();
+
+        for p in params.iter() {
+            if let Some(v) = p.vars().iter().find(|v| Type::is_flexible(v)) {
+                return Err(ParserError::new(&format!("{}: can't infer type param '{}'", id, v.split('\'').next().unwrap())))
+            }
+        }
+
+        Ok((params, arg_types.iter().map(|t| t.as_ref().map(|t| t.substitute(&bindings))).collect()))
     }
-}
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Statement {
-    Expression(Rc<Expression>),
-    Fun {
-        name:        Rc<String>,
-        param_names: Rc<Vec<Rc<String>>>,
-        param_types: Rc<Vec<Type>>,
-        t:           Option<Type>,
-        body:        Rc<Vec<Statement>>,
-    },
-}
+    // a field, or method of a class
+    pub fn field(&self, name: &str) -> Option<Type> {
+        match *self {
+            Type::Record(_, ref fields, _) | Type::Interface(_, ref fields) => fields.iter().find(|&(n, _)| n.as_str() == name).map(|(_, t)| t.clone()),
+            Type::Class(_, ref fields, ref methods, _) => fields.iter().chain(methods.iter()).find(|&(n, _)| n.as_str() == name).map(|(_, t)| t.clone()),
+            _ => None,
+        }
+    }
 
-impl Statement {
-    pub fn visit(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<()> {
+    // the user's implementation of a metamethod
+    pub fn operator(&self, name: &str) -> Option<Type> {
         match *self {
-            Statement::Expression(ref e) => e.visit(sym, env),
-            Statement::Fun { ref name, ref t, ref param_names, ref param_types, ref body, } => {
-                match sym.get_name(&name) {
-                    Some((_, _)) => return Err(ParserError::new(&format!("{}: already declared", name))),
-                    None => {
-                        let index = sym.add_name(&name);
-                        if index >= env.size() {
-                            env.grow();
-                        }
+            Type::Record(_, _, ref ops) | Type::Class(_, _, ref ops, _) => ops.iter().find(|&(n, _)| n.as_str() == name).map(|(_, t)| t.clone()),
+            _ => None,
+        }
+    }
 
-                        if let Err(e) = env.set_type(index, 0, try!(self.get_type(sym, env))) {
-                            panic!("error setting type: {}", e)
-                        }
-                    },
-                }
+    // a record or class as declared, its members only see it half done
+    pub fn complete(&self, sym: &Rc<SymTab>) -> Type {
+        match *self {
+            Type::Record(ref name, ref fields, _) => match sym.get_definition(name) {
+                Some(Type::Record(_, _, ops)) => Type::Record(name.clone(), fields.clone(), ops),
+                _ => self.clone(),
+            },
+            Type::Class(ref name, _, _, _) => match sym.get_definition(name) {
+                Some(t @ Type::Class(..)) => t,
+                _ => self.clone(),
+            },
+            _ => self.clone(),
+        }
+    }
 
-                let local_sym = Rc::new(SymTab::new(sym.clone(), &param_names));
-                let local_env = Rc::new(TypeTab::new(env.clone(), &param_types));
+    // what awaiting it gives
+    pub fn awaited(&self) -> Type {
+        match *self {
+            Type::Async(ref t) => (**t).clone(),
+            ref t              => t.clone(),
+        }
+    }
 
-                if let &Some(ref t) = t {
-                    for statement in body.iter() {
-                        statement.visit(&local_sym, &local_env)?;
-                        if statement.get_type(&local_sym, &local_env)? != *t {
-                            match *t {
-                                Type::Any => (),
-                                _ => return Err(ParserError::new(&format!("{}: mismatched return type", name))),
-                            }
-                        }
-                    }
-                }
-                
-                Ok(())
-            },
+    // the class or one of its parents is called name
+    pub fn extends(&self, name: &str) -> bool {
+        match *self {
+            Type::Class(ref n, _, _, ref parent) => n.as_str() == name || parent.as_ref().is_some_and(|p| p.extends(name)),
+            _ => false,
         }
     }
 
-    pub fn get_type(&self, sym: &Rc<SymTab>, env: &Rc<TypeTab>) -> ParserResult<Type> {
+    // several values, or just the one
+    pub fn tuple(mut types: Vec<Type>) -> Type {
+        match types.len() {
+            0 => Type::Nil,
+            1 => types.pop().unwrap(),
+            _ => Type::Tuple(Rc::new(types)),
+        }
+    }
+
+    // the first of several values
+    pub fn truncated(self) -> Type {
+        match self {
+            Type::Tuple(ref types) => types[0].clone(),
+            t                      => t,
+        }
+    }
+
+    // flattened, without duplicates and in a fixed order; nil makes it optional
+    pub fn union(types: Vec<Type>) -> Type {
+        let mut members: Vec<Type> = Vec::new();
+        let mut nil = false;
+
+        let mut pending = types;
+
+        while let Some(t) = pending.pop() {
+            match t {
+                Type::Any         => return Type::Any,
+                Type::Nil         => nil = true,
+                Type::Optional(t) => {
+                    nil = true;
+                    pending.push((*t).clone())
+                },
+                Type::Union(ts)   => pending.extend(ts.iter().cloned()),
+                t                 => if !members.contains(&t) {
+                    members.push(t)
+                },
+            }
+        }
+
+        members.sort_by_key(|t| format!("{:?}", t));
+
+        let t = match members.len() {
+            0 => Type::Nil,
+            1 => members.pop().unwrap(),
+            _ => Type::Union(Rc::new(members)),
+        };
+
+        if nil {
+            t.optional()
+        } else {
+            t
+        }
+    }
+
+    // what's left of a type after comparing equal to a value of another
+    pub fn narrow(&self, to: &Type) -> Type {
         match *self {
-            Statement::Expression(ref e) => e.get_type(sym, env),
-            Statement::Fun {ref name, ref t, ref param_names, ref param_types, ref body, } => {
-                let mut tp = Vec::new();
-                
-                if let &Some(ref t) = t {
-                    tp.push(t.clone())
+            Type::Union(ref members) => {
+                let left: Vec<Type> = members.iter().filter(|m| m.compare(to)).cloned().collect();
+
+                if left.is_empty() {
+                    self.clone()
                 } else {
-                    tp.push(Type::Any)
+                    Type::union(left)
                 }
-                
-                for t in param_types.iter() {
-                    tp.push(t.clone())
+            },
+            Type::Optional(ref t) => t.narrow(to),
+            ref t => t.clone(),
+        }
+    }
+
+    // a lua condition on a value being of this type, by its shape; none where it can't be told
+    pub fn lua_test(&self, value: &str) -> Option<String> {
+        match *self {
+            Type::Any  => Some("true".to_string()),
+            Type::Nil  => Some(format!("{} == nil", value)),
+            Type::Str  => Some(format!("type({}) == \"string\"", value)),
+            Type::Num  => Some(format!("type({}) == \"number\"", value)),
+            Type::Bool => Some(format!("type({}) == \"boolean\"", value)),
+
+            // every field that can't be nil is there
+            Type::Record(_, ref fields, _) => {
+                let mut tests = vec![format!("type({}) == \"table\"", value)];
+
+                for (name, t) in fields.iter() {
+                    if !t.compare(&Type::Nil) {
+                        tests.push(format!("{}.{} ~= nil", value, name))
+                    }
                 }
 
-                Ok(Type::Fun(Rc::new(tp)))
+                Some(tests.join(" and "))
+            },
+
+            // an instance of the class or of a subclass
+            Type::Class(ref name, ..) => Some(format!(
+                "(function(m) while m do if m == {} then return true end m = getmetatable(m) and getmetatable(m).__index end return false end)(getmetatable({}))",
+                name, value,
+            )),
+
+            // variants without fields are numbers, when no variant has any
+            Type::Enum(_, ref variants) => if variants.iter().all(|(_, fields)| fields.is_empty()) {
+                let tests: Vec<String> = (1 ..= variants.len()).map(|i| format!("{} == {}", value, i)).collect();
+
+                Some(format!("({})", tests.join(" or ")))
+            } else {
+                let tests: Vec<String> = variants.iter().map(|(v, _)| format!("{}.tag == \"{}\"", value, v)).collect();
+
+                Some(format!("type({}) == \"table\" and ({})", value, tests.join(" or ")))
+            },
+
+            Type::Optional(ref t) => t.lua_test(value).map(|test| format!("({} == nil or {})", value, test)),
+
+            Type::Union(ref members) => {
+                let tests: Option<Vec<String>> = members.iter().map(|m| m.lua_test(value)).collect();
+
+                tests.map(|tests| format!("({})", tests.join(" or ")))
             },
+
+            _ => None,
         }
     }
 
-    pub fn lua(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    // a method without its self, as called on an instance
+    pub fn unbound(&self) -> Type {
         match *self {
-            Statement::Expression(ref e) => write!(f, "{}", e),
-            Statement::Fun {
-                ref name, ref t, ref param_names, ref param_types, ref body,
-            } => {
-                write!(f, "function")?;
-                
-                write!(f, "{}", name)?;
+            Type::Fun(ref params, required) if params.len() > 1 => {
+                let mut rest = vec![params[0].clone()];
+                rest.extend_from_slice(&params[2 ..]);
 
-                write!(f, "(")?;
+                Type::Fun(Rc::new(rest), required.saturating_sub(1))
+            },
+            ref t => t.clone(),
+        }
+    }
 
-                for e in param_names.iter() {
-                    write!(f, "{}", e)?;
-                    if e != param_names.last().unwrap() {
-                        write!(f, ",")?;
-                    }
-                }
-                
-                writeln!(f, ")")?;
-                
-                for s in body.iter() {
-                    if s == body.last().unwrap() {
-                        match *s {
-                            Statement::Expression(ref e) => { write!(f, "return {}\n", e)?; },
-                            _ => { write!(f, "{}", s)?; },
-                        }
-                    } else {
-                        write!(f, "{}", s)?;
-                    }
-                }
-                
-                write!(f, "end")
+    // T?, which is just T when it already allows nil
+    pub fn optional(&self) -> Type {
+        match *self {
+            Type::Optional(_) | Type::Nil | Type::Any => self.clone(),
+            ref t => Type::Optional(Rc::new(t.clone())),
+        }
+    }
+
+    // the fields of one of an enum's variants
+    pub fn fields(&self, variant: &str) -> Fields {
+        match *self {
+            Type::Enum(_, ref variants) => match variants.iter().find(|&(v, _)| v.as_str() == variant) {
+                Some((_, fields)) => fields.clone(),
+                None                   => Rc::new(Vec::new()),
             },
+            _ => Rc::new(Vec::new()),
         }
     }
-}
 
-impl fmt::Display for Statement {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.lua(f)
+    // every value of a finite type, as match cases
+    pub fn cases(&self) -> Option<Vec<String>> {
+        match *self {
+            Type::Bool                  => Some(vec!["true".to_string(), "false".to_string()]),
+            Type::Enum(_, ref variants) => Some(variants.iter().map(|(v, _)| format!("{}", v)).collect()),
+            Type::Variant(_, ref v)     => Some(vec![format!("{}", v)]),
+            _                           => None,
+        }
     }
-}
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Type {
-    Str, Num, Bool, Any, Nil, Array(Rc<Type>), Undefined,
-    Fun(Rc<Vec<Type>>), Many(Rc<Type>),
-}
+    // how parameters are seen from inside their fun
+    pub fn locals(param_types: &[Type], sym: &Rc<SymTab>) -> Vec<Type> {
+        param_types.iter().map(|t| match *t {
+            Type::Many(ref t) => Type::Array(t.clone()),
+            ref t             => t.complete(sym),
+        }).collect()
+    }
 
-#[allow(unused)]
-impl Type {
     pub fn compare(&self, other: &Type) -> bool {
         if self == &Type::Any || other == &Type::Any {
             true
         } else {
             match self {
-                &Type::Array(ref a) => match other {
-                    &Type::Array(ref b) if **b != Type::Nil => a.compare(b),
+                // any.. takes any table, keyed ones too
+                Type::Array(a) => match other {
+                    Type::Array(b) if **b != Type::Nil => a.compare(b),
+                    Type::Map(_, _) => **a == Type::Any,
+                    _ => false,
+                },
+
+                Type::Union(members) => match other {
+                    Type::Union(others) => others.iter().all(|o| members.iter().any(|m| m.compare(o))),
+                    _                        => members.iter().any(|m| m.compare(other)),
+                },
+
+                // returns are covariant and params contravariant, a fun may ignore trailing args
+                Type::Fun(params, _) => match other {
+                    &Type::Fun(ref others, required) => {
+                        let many = match others.last() {
+                            Some(Type::Many(t)) if others.len() > 1 => Some(&**t),
+                            _                                           => None,
+                        };
+
+                        let fixed = if many.is_some() { &others[1 .. others.len() - 1] } else { &others[1 ..] };
+
+                        let accepts = |(i, p): (usize, &Type)| {
+                            let p = match *p {
+                                Type::Many(ref t) => &**t,
+                                ref t             => t,
+                            };
+
+                            fixed.get(i).or(many).is_none_or(|o| o.compare(p))
+                        };
+
+                        params[0].compare(&others[0]) && required < params.len() && params[1 ..].iter().enumerate().all(accepts)
+                    },
+                    _ => false,
+                },
+
+                Type::Async(t) => match other {
+                    Type::Async(u) => t.compare(u),
+                    _ => false,
+                },
+
+                Type::Iter(t) => match other {
+                    Type::Iter(u) => t.compare(u),
+                    _ => false,
+                },
+
+                // instances of subclasses too
+                Type::Class(name, _, _, _) => other.extends(name),
+
+                // an empty [] is an empty map too
+                Type::Map(k, v) => match other {
+                    Type::Map(j, u) => k.compare(j) && v.compare(u),
+                    Type::Array(t)  => **t == Type::Nil,
+                    _ => false,
+                },
+
+                Type::Tuple(types) => match other {
+                    Type::Tuple(others) => types.len() == others.len() && types.iter().zip(others.iter()).all(|(t, u)| t.compare(u)),
+                    _ => false,
+                },
+
+                Type::Optional(t) => match other {
+                    Type::Nil         => true,
+                    Type::Optional(u) => t.compare(u),
+                    _                 => t.compare(other),
+                },
+
+                &Type::Enum(_, _) => match other {
+                    Type::Variant(e, _) => **e == *self,
+                    _ => self == other,
+                },
+
+                Type::Record(name, fields, _) => match other {
+                    Type::Record(other_name, others, _) => name == other_name && fields.len() == others.len() && fields.iter().zip(others.iter()).all(|((_, t), (_, u))| t.compare(u)),
+                    _ => false,
+                },
+
+                // anything with compatible fields conforms
+                Type::Interface(_, fields) => match other {
+                    &Type::Record(_, ref others, _) | &Type::Interface(_, ref others) => fields.iter().all(|(n, t)| others.iter().any(|(m, u)| m == n && t.compare(u))),
+                    // or methods, as seen on the instance
+                    Type::Class(_, others, methods, _) => fields.iter().all(|(n, t)| {
+                        others.iter().any(|(m, u)| m == n && t.compare(u)) || methods.iter().any(|(m, u)| m == n && t.compare(&u.unbound()))
+                    }),
+                    // keyed tables, when their values fit every member
+                    Type::Map(k, v) => k.compare(&Type::Str) && fields.iter().all(|(_, t)| t.compare(v)),
                     _ => false,
                 },
 
@@ -535,6 +3190,7 @@ pub enum Operand {
     Pow,
     Mul, Div, Mod,
     Add, Sub,
+    Concat,
     Equal, NEqual,
     Lt, Gt, LtEqual, GtEqual,
     And, Or, Not,
@@ -542,6 +3198,57 @@ pub enum Operand {
 
 impl Operand {
     pub fn operate(&self, lr: (Type, Type)) -> ParserResult<Type> {
+        match (self, &lr) {
+            // a default for a value that might be nil
+            (&Operand::Or, &(Type::Optional(ref t), ref u)) if t.compare(u) => return match *u {
+                Type::Optional(_) | Type::Nil => Ok(t.optional()),
+                _                             => Ok((**t).clone()),
+            },
+
+            (&Operand::Equal, _) | (&Operand::NEqual, _) | (&Operand::And, _) | (&Operand::Or, _) | (&Operand::Not, _) => (),
+
+            (_, &(Type::Optional(_), _)) | (_, &(_, Type::Optional(_))) => {
+                return Err(ParserError::new(&format!("failed to '{:?} {} {:?}': might be nil", lr.0, self, lr.1)))
+            },
+
+            _ => (),
+        }
+
+        // every member has to support it
+        match lr {
+            (Type::Union(ref members), ref right) => {
+                let results: ParserResult<Vec<Type>> = members.iter().map(|m| self.operate((m.clone(), right.clone()))).collect();
+                return Ok(Type::union(results?))
+            },
+            (ref left, Type::Union(ref members)) => {
+                let results: ParserResult<Vec<Type>> = members.iter().map(|m| self.operate((left.clone(), m.clone()))).collect();
+                return Ok(Type::union(results?))
+            },
+            _ => (),
+        }
+
+        // user implementations come first, lua flips > into <
+        if let Some(name) = self.metamethod() {
+            let (a, b) = match *self {
+                Operand::Gt | Operand::GtEqual => (&lr.1, &lr.0),
+                _                              => (&lr.0, &lr.1),
+            };
+
+            if let Some(Type::Fun(ref params, _)) = a.operator(name) {
+                match *self {
+                    _ if params.len() > 2 && params[2].compare(b) => return Ok(match *self {
+                        Operand::Equal | Operand::NEqual | Operand::Lt | Operand::Gt | Operand::LtEqual | Operand::GtEqual => Type::Bool,
+                        _ => params[0].clone(),
+                    }),
+
+                    // lua only compares tables of the same kind
+                    Operand::Equal | Operand::NEqual => (),
+
+                    _ => return Err(ParserError::new(&format!("failed to '{:?} {} {:?}': '{}' takes '{:?}'", lr.0, self, lr.1, name, params.get(2).unwrap_or(&Type::Nil)))),
+                }
+            }
+        }
+
         match *self {
             Operand::Pow => match lr {
                 (Type::Num, Type::Num) => Ok(Type::Num),
@@ -600,13 +3307,33 @@ impl Operand {
                 (a, b) => Err(ParserError::new(&format!("failed to subtract: {:?} and {:?}", a, b))),
             },
 
+            Operand::Concat => match lr {
+                (Type::Str, Type::Str)  => Ok(Type::Str),
+                (Type::Str, Type::Num)  => Ok(Type::Str),
+                (Type::Num, Type::Str)  => Ok(Type::Str),
+                (Type::Num, Type::Num)  => Ok(Type::Str),
+                (Type::Any, Type::Str)  => Ok(Type::Str),
+                (Type::Str, Type::Any)  => Ok(Type::Str),
+                (Type::Any, Type::Num)  => Ok(Type::Any),
+                (Type::Num, Type::Any)  => Ok(Type::Any),
+                (Type::Any, Type::Any)  => Ok(Type::Any),
+                (a, b) => Err(ParserError::new(&format!("failed to concat: {:?} and {:?}", a, b))),
+            },
+
             Operand::Equal | Operand::NEqual => Ok(Type::Bool),
 
             Operand::Lt | Operand::Gt | Operand::LtEqual | Operand::GtEqual => match lr {
-                (a @ Type::Bool, b @ _) => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
-                (a @ _, b @ Type::Bool) => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
-                (a @ Type::Str, b @ _)  => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
-                (a @ _, b @ Type::Str)  => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
+                (a @ Type::Bool, b) => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
+                (a, b @ Type::Bool) => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
+                (a @ Type::Str, b)  => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
+                (a, b @ Type::Str)  => Err(ParserError::new(&format!("failed to '{:?} < {:?}'", a, b))),
+                // lua 5.4 doesn't derive __le from __lt
+                (ref a @ Type::Record(..), ref b) | (ref a @ Type::Class(..), ref b) |
+                (ref a, ref b @ Type::Record(..)) | (ref a, ref b @ Type::Class(..)) => {
+                    let op = if self.metamethod() == Some("__le") { "<=" } else { "<" };
+
+                    Err(ParserError::new(&format!("failed to '{:?} {} {:?}': needs 'op {}'", a, self, b, op)))
+                },
                 _ => Ok(Type::Bool),
             },
 
@@ -614,6 +3341,23 @@ impl Operand {
         }
     }
 
+    // what lua calls on tables for it
+    pub fn metamethod(&self) -> Option<&'static str> {
+        match *self {
+            Operand::Pow                       => Some("__pow"),
+            Operand::Mul                       => Some("__mul"),
+            Operand::Div                       => Some("__div"),
+            Operand::Mod                       => Some("__mod"),
+            Operand::Add                       => Some("__add"),
+            Operand::Sub                       => Some("__sub"),
+            Operand::Concat                    => Some("__concat"),
+            Operand::Equal | Operand::NEqual   => Some("__eq"),
+            Operand::Lt | Operand::Gt          => Some("__lt"),
+            Operand::LtEqual | Operand::GtEqual => Some("__le"),
+            Operand::And | Operand::Or | Operand::Not => None,
+        }
+    }
+
     pub fn lua(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Operand::Pow     => write!(f, "^"),
@@ -622,6 +3366,7 @@ impl Operand {
             Operand::Mod     => write!(f, "%"),
             Operand::Add     => write!(f, "+"),
             Operand::Sub     => write!(f, "-"),
+            Operand::Concat  => write!(f, ".."),
             Operand::Equal   => write!(f, "=="),
             Operand::NEqual  => write!(f, "~="),
             Operand::Lt      => write!(f, "<"),
@@ -649,15 +3394,16 @@ pub fn get_operand(v: &str) -> Option<(Operand, u8)> {
         "%"   => Some((Operand::Mod, 1)),
         "+"   => Some((Operand::Add, 2)),
         "-"   => Some((Operand::Sub, 2)),
-        "=="  => Some((Operand::Equal, 3)),
-        "!="  => Some((Operand::NEqual, 3)),
+        "++"  => Some((Operand::Concat, 3)),
+        "=="  => Some((Operand::Equal, 4)),
+        "!="  => Some((Operand::NEqual, 4)),
         "<"   => Some((Operand::Lt, 4)),
         ">"   => Some((Operand::Gt, 4)),
         "<="  => Some((Operand::LtEqual, 4)),
         ">="  => Some((Operand::GtEqual, 4)),
-        "!"   => Some((Operand::Not, 4)),
-        "and" => Some((Operand::And, 4)),
-        "or"  => Some((Operand::Or, 4)),
+        "!"   => Some((Operand::Not, 5)),
+        "and" => Some((Operand::And, 6)),
+        "or"  => Some((Operand::Or, 7)),
         _ => None,
     }
 }
@@ -2,12 +2,12 @@ use super::lexer::TokenPosition;
 
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParserErrorValue {
     Constant(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ParserError {
     value:    ParserErrorValue,
     position: Option<TokenPosition>,
@@ -1,12 +1,15 @@
 pub mod error;
 pub mod traveler;
 pub mod ast;
+#[allow(clippy::module_inception)]
 pub mod parser;
+pub mod runtime;
 
 pub use self::error::*;
 pub use self::traveler::*;
 pub use self::ast::*;
 pub use self::parser::*;
+pub use self::runtime::*;
 
 pub type ParserResult<T> = Result<T, ParserError>;
 
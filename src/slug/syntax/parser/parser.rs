@@ -1,11 +1,28 @@
 use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 
 use super::*;
 use super::ParserError;
-use super::lexer::TokenType;
+use super::lexer::{Token, TokenPosition, TokenType};
+use super::super::Loader;
+
+// names, types and defaults of a fun's parameters
+type Params = (Vec<Rc<String>>, Vec<Type>, Vec<Option<Rc<Expression>>>);
+
+// type params of generic records, by record name
+type Generics = HashMap<String, Rc<Vec<Rc<String>>>>;
 
 pub struct Parser {
-    traveler: Traveler,
+    traveler:    Traveler,
+    types:       Rc<RefCell<HashMap<String, Type>>>, // declared types, shared with block parsers
+    generics:    Rc<RefCell<Generics>>,
+    diagnostics: Rc<RefCell<Vec<ParserError>>>, // errors recovered from, shared with block parsers
+    superclass:  Rc<RefCell<Option<Rc<String>>>>, // parent of the class being parsed, for super
+    constructors: Rc<RefCell<HashMap<String, Fields>>>, // what each class's new takes, for subclasses without one
+    yields:      Rc<Cell<Option<bool>>>, // whether the fun being parsed yields, none outside funs
+    header:      bool, // inside an if, match or for header, up to its ':'
+    loader:      Option<Rc<Loader>>, // for the types of imported modules
 }
 
 #[allow(dead_code)]
@@ -13,25 +30,196 @@ impl Parser {
     pub fn new(traveler: Traveler) -> Parser {
         Parser {
             traveler,
+            types:       Rc::new(RefCell::new(HashMap::new())),
+            generics:    Rc::new(RefCell::new(HashMap::new())),
+            diagnostics: Rc::new(RefCell::new(Vec::new())),
+            superclass:  Rc::new(RefCell::new(None)),
+            constructors: Rc::new(RefCell::new(HashMap::new())),
+            yields:      Rc::new(Cell::new(None)),
+            header:      false,
+            loader:      None,
+        }
+    }
+
+    // a parser for a module, which may import other modules
+    pub fn new_module(traveler: Traveler, loader: Rc<Loader>) -> Parser {
+        Parser {
+            loader: Some(loader),
+            ..Parser::new(traveler)
+        }
+    }
+
+    // a type declared while parsing, by name
+    pub fn declared_type(&self, name: &str) -> Option<Type> {
+        self.types.borrow().get(name).cloned()
+    }
+
+    // a parser for a nested block, seeing the same declared types
+    fn sub(&self, tokens: Vec<Token>) -> Parser {
+        Parser {
+            traveler:    Traveler::new(tokens),
+            types:       self.types.clone(),
+            generics:    self.generics.clone(),
+            diagnostics: self.diagnostics.clone(),
+            superclass:  self.superclass.clone(),
+            constructors: self.constructors.clone(),
+            yields:      self.yields.clone(),
+            header:      false,
+            loader:      self.loader.clone(),
         }
     }
 
-    pub fn parse(&mut self) -> ParserResult<Vec<Statement>> {
+    // everything that parsed, with error nodes where it didn't, and the errors
+    pub fn parse(&mut self) -> (Vec<Statement>, Vec<ParserError>) {
+        let stack = self.statements();
+
+        (stack, self.diagnostics.borrow_mut().drain(..).collect())
+    }
+
+    fn statements(&mut self) -> Vec<Statement> {
         let mut stack = Vec::new();
         while self.traveler.remaining() > 2 {
-            if let Some(s) = self.statement()? {
-                stack.push(s);
-                self.traveler.next();
-            } else {
-                stack.push(Statement::Expression(Rc::new(self.expression()?)))
+            let start = self.traveler.checkpoint();
+
+            let parsed = match self.statement() {
+                Ok(Some(s)) => {
+                    self.traveler.next();
+                    Ok(s)
+                },
+                Ok(None) => self.expression().map(|e| Statement::Expression(Rc::new(e))),
+                Err(e)   => Err(e),
+            };
+
+            match parsed {
+                Ok(s)  => stack.push(s),
+                Err(e) => {
+                    self.diagnostics.borrow_mut().push(e);
+                    stack.push(Statement::Error(Rc::new(self.failed_names(start))));
+
+                    self.synchronize(start)
+                },
+            }
+        }
+
+        stack
+    }
+
+    // skips a failed statement, up to the next line at this indent
+    fn synchronize(&mut self, start: usize) {
+        self.traveler.restore(start);
+
+        while self.traveler.remaining() > 2 && self.is_newline() {
+            self.traveler.next();
+        }
+
+        let line = self.traveler.checkpoint();
+
+        // up to a newline after the closing brackets
+        let mut depth = 0;
+
+        while self.traveler.remaining() > 2 {
+            match self.traveler.current_content().as_str() {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => depth -= 1,
+                _ => (),
+            }
+
+            self.traveler.next();
+
+            if depth <= 0 && self.is_newline() {
+                // the line's indented block failed with it
+                if self.block_follows() {
+                    self.traveler.next();
+                    continue
+                }
+
+                return
+            }
+        }
+
+        // a bracket that's never closed, the line ends it
+        self.traveler.restore(line);
+
+        while self.traveler.remaining() > 2 && !self.is_newline() {
+            self.traveler.next();
+        }
+
+        if self.block_follows() {
+            self.traveler.next();
+            self.traveler.next();
+        }
+    }
+
+    // an indented block right after this newline
+    fn block_follows(&self) -> bool {
+        let next = self.traveler.checkpoint() + 1;
+
+        next < self.traveler.tokens.len() && matches!(self.traveler.tokens[next].token_type, TokenType::Block(_))
+    }
+
+    // the names a failed statement would have declared
+    fn failed_names(&self, start: usize) -> Vec<Rc<String>> {
+        let tokens = &self.traveler.tokens;
+        let mut i  = start;
+
+        let content = |i: usize| tokens.get(i).map(|t| t.content().as_str()).unwrap_or("");
+        let name    = |i: usize| tokens.get(i).filter(|t| t.token_type == TokenType::Identifier).map(|t| Rc::new(t.content().clone()));
+
+        while i < tokens.len() && (content(i) == "\n" || tokens[i].token_type == TokenType::Eol) {
+            i += 1
+        }
+
+        while content(i) == "export" || content(i) == "async" {
+            i += 1
+        }
+
+        match content(i) {
+            "fun" | "record" | "enum" | "class" => return name(i + 1).into_iter().collect(),
+            _ => (),
+        }
+
+        // a, b = or a num = up to the line's '='
+        let mut names = Vec::new();
+        let mut first = true;
+
+        while i < tokens.len() && content(i) != "\n" && tokens[i].token_type != TokenType::Eol {
+            match content(i) {
+                "=" => return names,
+                "," => first = true,
+                _   => {
+                    if first {
+                        names.extend(name(i))
+                    }
+
+                    first = false
+                },
             }
+
+            i += 1
         }
 
-        Ok(stack)
+        Vec::new()
+    }
+
+    fn is_newline(&self) -> bool {
+        self.traveler.current_content() == "\n" || self.traveler.current().token_type == TokenType::Eol
     }
     
+    // no space between the previous token and this one
+    fn is_attached(&self) -> bool {
+        let i = self.traveler.checkpoint();
+
+        if i == 0 {
+            return false
+        }
+
+        let (prev, current) = (&self.traveler.tokens[i - 1], self.traveler.current());
+
+        prev.position.line == current.position.line && prev.position.col + prev.content().len() == current.position.col
+    }
+
     pub fn skip_whitespace(&mut self) -> ParserResult<()> {
-        while self.traveler.current_content() == "\n" || self.traveler.current().token_type == TokenType::EOL {
+        while self.traveler.current_content() == "\n" || self.traveler.current().token_type == TokenType::Eol {
             self.traveler.next();
             
             if self.traveler.remaining() < 2 {
@@ -44,7 +232,7 @@ impl Parser {
     
     pub fn statement(&mut self) -> ParserResult<Option<Statement>> {
         match self.traveler.current().token_type {
-            TokenType::EOL => {
+            TokenType::Eol => {
                 self.traveler.next();
                 self.statement()
             },
@@ -57,461 +245,1961 @@ impl Parser {
                         let name = Rc::new(self.traveler.current_content());
                         self.traveler.next();
 
+                        Ok(Some(self.fun_rest(name)?))
+                    } else {
+                        self.traveler.prev();
+
+                        Ok(Some(Statement::Expression(Rc::new(self.expression()?))))
+                    }
+                },
+                "match" | "if" | "super" | "throw" | "await" | "yield" => Ok(Some(Statement::Expression(Rc::new(self.expression()?)))),
+                // for k, v in m
+                "for" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+
+                    let mut names = vec![Rc::new(self.traveler.expect(TokenType::Identifier)?)];
+                    self.traveler.next();
+
+                    while self.traveler.current_content() == "," {
+                        self.traveler.next();
+
+                        names.push(Rc::new(self.traveler.expect(TokenType::Identifier)?));
+                        self.traveler.next();
+                    }
+
+                    if names.len() > 2 {
+                        return Err(ParserError::new_pos(position, "expected at most two names to iterate with"))
+                    }
+
+                    self.traveler.expect_content("in")?;
+                    self.traveler.next();
+
+                    let iterable = Rc::new(self.header()?);
+                    self.traveler.next();
+
+                    self.traveler.expect_content(":")?;
+                    self.traveler.next();
+
+                    Ok(Some(Statement::For {
+                        names: Rc::new(names),
+                        iterable,
+                        body: Rc::new(self.body()?),
+                        over: Rc::new(Cell::new(Iteration::Array)),
+                    }))
+                },
+                // import ui.button as button
+                "import" => {
+                    self.traveler.next();
+
+                    let mut parts = vec![self.traveler.expect(TokenType::Identifier)?];
+                    self.traveler.next();
+
+                    while self.traveler.current_content() == "." {
+                        self.traveler.next();
+
+                        parts.push(self.traveler.expect(TokenType::Identifier)?);
+                        self.traveler.next();
+                    }
+
+                    let name = if self.traveler.current_content() == "as" {
+                        self.traveler.next();
+                        self.traveler.expect(TokenType::Identifier)?
+                    } else {
+                        self.traveler.prev();
+                        parts.last().unwrap().clone()
+                    };
+
+                    let path = parts.join(".");
+
+                    // button.Btn names a type the module exports
+                    if let Some(ref loader) = self.loader {
+                        for (t_name, t) in loader.load_types(&path)?.iter() {
+                            self.types.borrow_mut().insert(format!("{}.{}", name, t_name), t.clone());
+                        }
+                    }
+
+                    Ok(Some(Statement::Import {
+                        path: Rc::new(path),
+                        name: Rc::new(name),
+                    }))
+                },
+                "export" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+
+                    let exported = match self.statement()? {
+                        Some(s) => s,
+                        None    => return Err(ParserError::new_pos(position, "expected something to export")),
+                    };
+
+                    if exported.exported_names().is_empty() {
+                        return Err(ParserError::new_pos(position, "can only export funs, records, enums, classes and definitions"))
+                    }
+
+                    Ok(Some(Statement::Export(Rc::new(exported))))
+                },
+                // return a, b
+                "return" => {
+                    self.traveler.next();
+
+                    let mut values = Vec::new();
+
+                    while self.traveler.current_content() != "\n" && self.traveler.current().token_type != TokenType::Eof {
+                        values.push(self.expression()?);
+                        self.traveler.next();
+
+                        if self.traveler.current_content() != "," {
+                            break
+                        }
+
+                        self.traveler.next();
+                    }
+
+                    self.traveler.prev();
+
+                    Ok(Some(Statement::Return(Rc::new(values))))
+                },
+                "record" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+
+                    let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+                    self.traveler.next();
+
+                    let generics = self.generics()?;
+                    let shadowed = self.bind_generics(&generics);
+
+                    self.traveler.expect_content(":")?;
+                    self.traveler.next();
+
+                    // operators may take the record itself
+                    self.declare_type(position, &name, Type::Record(name.clone(), Rc::new(Vec::new()), Rc::new(Vec::new())))?;
+
+                    let mut fields = Vec::new();
+                    let mut ops    = Vec::new();
+
+                    let funs = if self.traveler.current_content() == "\n" {
+                        self.traveler.next();
+
+                        match self.traveler.current().token_type.clone() {
+                            TokenType::Block(ref v) => self.sub(v.clone()).members(&name, false, &mut fields, &mut ops, |f, o| Type::Record(name.clone(), Rc::new(f.to_vec()), Rc::new(o.to_vec())))?,
+                            _ => Vec::new(),
+                        }
+                    } else {
+                        fields = self.fields(false)?;
+                        self.traveler.prev();
+
+                        Vec::new()
+                    };
+
+                    if fields.is_empty() {
+                        return Err(ParserError::new_pos(position, &format!("{}: expected fields", name)))
+                    }
+
+                    self.unbind_generics(shadowed);
+
+                    let fields = Rc::new(fields);
+
+                    self.types.borrow_mut().insert(name.to_string(), Type::Record(name.clone(), fields.clone(), Rc::new(ops)));
+
+                    if !generics.is_empty() {
+                        self.generics.borrow_mut().insert(name.to_string(), Rc::new(generics));
+                    }
+
+                    Ok(Some(Statement::Record {
+                        name,
+                        fields,
+                        ops: Rc::new(funs),
+                    }))
+                },
+                "interface" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+
+                    let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+                    self.traveler.next();
+
+                    self.traveler.expect_content(":")?;
+                    self.traveler.next();
+
+                    let fields = if self.traveler.current_content() == "\n" {
+                        self.traveler.next();
+
+                        match self.traveler.current().token_type.clone() {
+                            TokenType::Block(ref v) => self.sub(v.clone()).fields(true)?,
+                            _ => Vec::new(),
+                        }
+                    } else {
+                        let fields = self.fields(false)?;
+                        self.traveler.prev();
+
+                        fields
+                    };
+
+                    if fields.is_empty() {
+                        return Err(ParserError::new_pos(position, &format!("{}: expected fields", name)))
+                    }
+
+                    let fields = Rc::new(fields);
+
+                    self.declare_type(position, &name, Type::Interface(name.clone(), fields.clone()))?;
+
+                    Ok(Some(Statement::Interface {
+                        name,
+                        fields,
+                    }))
+                },
+                // async fun, its callers get a task to await
+                "async" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+
+                    if self.traveler.current_content() != "fun" {
+                        return Err(ParserError::new_pos(position, &format!("expected fun after async, found: {}", self.traveler.current_content())))
+                    }
+
+                    match self.statement()? {
+                        Some(Statement::Fun { t: Some(Type::Iter(_)), .. }) => Err(ParserError::new_pos(position, "async funs can't yield")),
+                        Some(Statement::Fun { name, param_names, param_types, param_defaults, t, body, }) => Ok(Some(Statement::Fun {
+                            name,
+                            param_names,
+                            param_types,
+                            param_defaults,
+                            t: Some(Type::Async(Rc::new(t.unwrap_or(Type::Any)))),
+                            body,
+                        })),
+                        _ => Err(ParserError::new_pos(position, "expected a named fun after async")),
+                    }
+                },
+                // try: ... catch err: ... finally: ...
+                "try" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+
+                    self.traveler.expect_content(":")?;
+                    self.traveler.next();
+
+                    let body = Rc::new(self.body()?);
+
+                    let mut catch   = None;
+                    let mut finally = None;
+
+                    loop {
+                        let checkpoint = self.traveler.checkpoint();
+
+                        self.traveler.next();
+                        self.skip_whitespace()?;
+
                         match self.traveler.current_content().as_str() {
-                            "(" => {
+                            "catch" if catch.is_none() && finally.is_none() => {
                                 self.traveler.next();
 
-                                let mut param_names = Vec::new();
-                                let mut param_types = Vec::new();
-                                
-                                while self.traveler.current_content() != ")" {
-                                    param_names.push(Rc::new(self.traveler.expect(TokenType::Identifier)?));
-                                    self.traveler.next();
-
-                                    if self.traveler.current().token_type == TokenType::Type {
-                                        param_types.push(get_type(&self.traveler.expect(TokenType::Type)?).unwrap());
-                                        self.traveler.next();
-                                    } else {
-                                        param_types.push(Type::Any);
-                                    }
+                                let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+                                self.traveler.next();
 
-                                    if self.traveler.current_content() == "," {
-                                        self.traveler.next();
+                                let t = if self.traveler.current_content() == ":" {
+                                    Type::Any
+                                } else {
+                                    match self.types()? {
+                                        Some(t) => t,
+                                        None    => return Err(ParserError::new_pos(self.traveler.current().position, &format!("{}: expected error type, found: {}", name, self.traveler.current_content()))),
                                     }
-                                }
-                                
-                                self.traveler.next();
-                                
-                                let mut t = None;
-                                
-                                if self.traveler.current().token_type == TokenType::Type {
-                                    t = get_type(&self.traveler.current_content());
-                                    self.traveler.next();
-                                }
+                                };
 
                                 self.traveler.expect_content(":")?;
                                 self.traveler.next();
-                                
-                                let body;
-                                
-                                if self.traveler.current_content() == "\n" {
-                                    self.traveler.next();
-
-                                    body = Rc::new(self.block()?);
-                                } else {                                    
-                                    body = Rc::new(vec![Statement::Expression(Rc::new(self.expression()?))])
-                                }
 
-                                Ok(Some(Statement::Fun {
+                                catch = Some(Catch {
                                     name,
-                                    param_names: Rc::new(param_names),
-                                    param_types: Rc::new(param_types),
                                     t,
-                                    body,
-                                }))
+                                    body: Rc::new(self.body()?),
+                                })
                             },
-
-                            _ => {
-                                let mut t = None;
-
-                                if self.traveler.current().token_type == TokenType::Type {
-                                    t = get_type(&self.traveler.current_content());
-                                    self.traveler.next();
-                                }
-
+                            "finally" if finally.is_none() => {
+                                self.traveler.next();
                                 self.traveler.expect_content(":")?;
                                 self.traveler.next();
 
-                                let body;
+                                finally = Some(Rc::new(self.body()?))
+                            },
+                            _ => {
+                                self.traveler.restore(checkpoint);
 
-                                if self.traveler.current_content() == "\n" {
-                                    self.traveler.next();
-                                    
-                                    body = Rc::new(self.block()?);
-                                } else {
-                                    body = Rc::new(vec![Statement::Expression(Rc::new(self.expression()?))])
-                                }
-                                
-                                Ok(Some(Statement::Fun {
-                                    name,
-                                    param_names: Rc::new(Vec::new()),
-                                    param_types: Rc::new(Vec::new()),
-                                    t,
-                                    body,
-                                }))
+                                break
                             },
                         }
+                    }
 
-                    } else {
-                        if self.traveler.current().token_type == TokenType::Type {
-                            self.traveler.prev();
-                            
-                            Ok(Some(Statement::Expression(Rc::new(self.expression()?))))
-                        } else {
-                            self.traveler.prev();
-                            Ok(None)
-                        }
+                    if catch.is_none() && finally.is_none() {
+                        return Err(ParserError::new_pos(position, "try: expected catch or finally"))
                     }
+
+                    Ok(Some(Statement::Try {
+                        body,
+                        catch,
+                        finally,
+                    }))
                 },
-                _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected: {}", self.traveler.current_content()))),
-            },
-            
-            _ => Ok(Some(Statement::Expression(Rc::new(self.expression()?)))),
-        }
-    }
-    
-    pub fn term(&mut self) -> ParserResult<Expression> {
-        self.skip_whitespace()?;
-        match self.traveler.current().token_type {
-            TokenType::EOL => return Ok(Expression::EOF),
+                // class Enemy extends Entity:
+                "class" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
 
-            TokenType::Block(_) => {
-                let block = self.block()?;
-                
-                if block.len() > 1 {
-                    return Err(ParserError::new_pos(self.traveler.current().position, &format!("can't termize several elements")))
-                } else {
-                    match block.get(0) {
-                        Some(s) => match *s {
-                            Statement::Expression(ref e) => {
-                                let ref ex = *e.clone();
-                                return Ok(ex.clone())
-                            },
-                            _ => (),
-                        },
-                        None => return Err(ParserError::new_pos(self.traveler.current().position, &format!("")))
-                    }
-                    return Ok(Expression::Block(Rc::new(self.block()?)))
-                }
-            },
+                    let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+                    self.traveler.next();
+
+                    let parent = if self.traveler.current_content() == "extends" {
+                        self.traveler.next();
+
+                        let parent_position = self.traveler.current().position;
+                        let parent = self.traveler.current_content();
+
+                        match self.types.borrow().get(&parent) {
+                            Some(&Type::Class(..)) => (),
+                            _ => return Err(ParserError::new_pos(parent_position, &format!("{}: can only extend classes, found: {}", name, parent))),
+                        }
+
+                        self.traveler.next();
+
+                        Some(Rc::new(parent))
+                    } else {
+                        None
+                    };
+
+                    self.traveler.expect_content(":")?;
+                    self.traveler.next();
+
+                    let parent_t = parent.as_ref().and_then(|p| self.types.borrow().get(p.as_str()).cloned());
+
+                    // what the class gets from its parent
+                    let (mut fields, mut methods) = match parent_t {
+                        Some(Type::Class(_, ref fields, ref methods, _)) => ((**fields).clone(), (**methods).clone()),
+                        _ => (Vec::new(), Vec::new()),
+                    };
+
+                    let parent_t = parent_t.map(Rc::new);
+
+                    // the class is a type while its body is parsed, growing with each member
+                    self.declare_type(position, &name, Type::Class(name.clone(), Rc::new(fields.clone()), Rc::new(methods.clone()), parent_t.clone()))?;
+
+                    let shadowed = self.superclass.replace(parent.clone());
+
+                    let members = if self.traveler.current_content() == "\n" {
+                        self.traveler.next();
+
+                        match self.traveler.current().token_type.clone() {
+                            TokenType::Block(ref v) => self.sub(v.clone()).members(&name, true, &mut fields, &mut methods, |f, m| Type::Class(name.clone(), Rc::new(f.to_vec()), Rc::new(m.to_vec()), parent_t.clone())),
+                            _ => Ok(Vec::new()),
+                        }
+                    } else {
+                        Err(ParserError::new_pos(position, &format!("{}: expected members on the lines below", name)))
+                    };
+
+                    *self.superclass.borrow_mut() = shadowed;
+
+                    let members = members?;
+
+                    let t = Type::Class(name.clone(), Rc::new(fields), Rc::new(methods), parent_t);
+
+                    let base = match parent {
+                        Some(ref parent) => self.constructors.borrow().get(parent.as_str()).cloned().unwrap_or_default(),
+                        None             => Rc::new(Vec::new()),
+                    };
+
+                    let constructor = members.iter().find_map(|m| match *m {
+                        Statement::Fun { name: ref m, ref param_names, ref param_types, .. } if m.as_str() == "new" => {
+                            Some(param_names.iter().cloned().zip(param_types.iter().cloned()).collect::<Vec<_>>())
+                        },
+                        _ => None,
+                    });
+
+                    // a generated new passes the parent's args on, which can't come before the class's own
+                    let constructor = match constructor {
+                        Some(params) => params,
+                        None => match base.last() {
+                            Some((_, Type::Many(_))) => return Err(ParserError::new_pos(position, &format!("{}: needs a new, {}'s takes variadic args", name, parent.unwrap()))),
+                            _ => {
+                                let own = own_fields(&t);
+
+                                if let Some((field, _)) = own.iter().find(|(n, _)| base.iter().any(|(b, _)| b == n)) {
+                                    return Err(ParserError::new_pos(position, &format!("{}: needs a new, {}'s also takes '{}'", name, parent.unwrap(), field)))
+                                }
+
+                                base.iter().cloned().chain(own).collect()
+                            },
+                        },
+                    };
+
+                    self.constructors.borrow_mut().insert(name.to_string(), Rc::new(constructor));
+
+                    Ok(Some(Statement::Class {
+                        name,
+                        parent,
+                        t,
+                        methods: Rc::new(members),
+                        base,
+                    }))
+                },
+                "enum" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+
+                    let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+                    self.traveler.next();
+
+                    self.traveler.expect_content(":")?;
+                    self.traveler.next();
+
+                    let variants = if self.traveler.current_content() == "\n" {
+                        self.traveler.next();
+
+                        match self.traveler.current().token_type.clone() {
+                            TokenType::Block(ref v) => self.sub(v.clone()).variants(true)?,
+                            _ => Vec::new(),
+                        }
+                    } else {
+                        let variants = self.variants(false)?;
+                        self.traveler.prev();
+
+                        variants
+                    };
+
+                    if variants.is_empty() {
+                        return Err(ParserError::new_pos(position, &format!("{}: expected variants", name)))
+                    }
+
+                    let variants = Rc::new(variants);
+
+                    self.declare_type(position, &name, Type::Enum(name.clone(), variants.clone()))?;
+
+                    Ok(Some(Statement::Enum {
+                        name,
+                        variants,
+                    }))
+                },
+                _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected: {}", self.traveler.current_content()))),
+            },
+            
+            // type Name = t, only a statement when it looks like one
+            TokenType::Identifier if self.traveler.current_content() == "type" && self.is_alias() => {
+                let position = self.traveler.current().position;
+                self.traveler.next();
+
+                let name = Rc::new(self.traveler.current_content());
+                self.traveler.next();
+
+                let generics = self.generics()?;
+                let shadowed = self.bind_generics(&generics);
+
+                self.traveler.expect_content("=")?;
+                self.traveler.next();
+
+                let t = match self.types()? {
+                    Some(t) => t,
+                    None    => return Err(ParserError::new_pos(position, &format!("{}: expected type, found: {}", name, self.traveler.current_content()))),
+                };
+
+                self.unbind_generics(shadowed);
+
+                self.declare_type(position, &name, t.clone())?;
+
+                if !generics.is_empty() {
+                    self.generics.borrow_mut().insert(name.to_string(), Rc::new(generics));
+                }
+
+                self.traveler.prev();
+
+                Ok(Some(Statement::Alias {
+                    name,
+                    t,
+                }))
+            },
+
+            _ => {
+                let expr = self.expression()?;
+
+                self.traveler.next();
+
+                if self.traveler.current_content() == "," {
+                    return Ok(Some(Statement::Expression(Rc::new(self.multi_definition(expr)?))))
+                }
+
+                self.traveler.prev();
+
+                Ok(Some(Statement::Expression(Rc::new(expr))))
+            },
+        }
+    }
+
+    fn multi_definition(&mut self, first: Expression) -> ParserResult<Expression> {
+        let mut targets = vec![first];
+        let mut values  = Vec::new();
+
+        while self.traveler.current_content() == "," {
+            self.traveler.next();
+
+            match self.expression()? {
+                Expression::Definition(None, ref target, Some(ref value), _) if values.is_empty() => {
+                    targets.push((**target).clone());
+                    values.push((**value).clone());
+                },
+                e => if values.is_empty() {
+                    targets.push(e)
+                } else {
+                    values.push(e)
+                },
+            }
+
+            self.traveler.next();
+        }
+
+        self.traveler.prev();
+
+        if values.is_empty() {
+            return Err(ParserError::new_pos(self.traveler.current().position, "expected '=' after names"))
+        }
+
+        Ok(Expression::MultiDefinition(Rc::new(targets), Rc::new(values), Declared::default()))
+    }
+    
+    pub fn term(&mut self) -> ParserResult<Expression> {
+        self.skip_whitespace()?;
+        match self.traveler.current().token_type {
+            TokenType::Eol => return Ok(Expression::Eof),
+
+            TokenType::Block(_) => {
+                let block = self.block()?;
+                
+                if block.len() > 1 {
+                    return Err(ParserError::new_pos(self.traveler.current().position, "can't termize several elements"))
+                } else {
+                    match block.first() {
+                        Some(s) => if let Statement::Expression(ref e) = *s {
+                            let ex = &*e.clone();
+                            return Ok(ex.clone())
+                        },
+                        None => return Err(ParserError::new_pos(self.traveler.current().position, ""))
+                    }
+                    return Ok(Expression::Block(Rc::new(self.block()?)))
+                }
+            },
+
+            _ => (),
+        }
+
+        match self.traveler.current().token_type {
+            TokenType::IntLiteral    => Ok(Expression::NumberLiteral(self.traveler.current_content().parse::<f64>().unwrap())),
+            TokenType::FloatLiteral  => Ok(Expression::NumberLiteral(self.traveler.current_content().parse::<f64>().unwrap())),
+            TokenType::BoolLiteral   => Ok(Expression::BoolLiteral(self.traveler.current_content() == "true")),
+            TokenType::StringLiteral => Ok(Expression::StringLiteral(Rc::new(self.traveler.current_content().clone()))),
+            TokenType::Identifier    => {
+                let id = Expression::Identifier(Rc::new(self.traveler.current_content()));
+
+                self.traveler.next();
+
+                if let Some(t) = self.annotation()? {                    
+                    match self.traveler.current_content().as_str() {
+                        "=" => {
+                            self.traveler.next();
+                            let expr = self.expression()?;
+                            
+                            return Ok(Expression::Definition(Some(t), Rc::new(id), Some(Rc::new(expr)), Declared::default()))
+                        },
+
+                        _ => return Ok(Expression::Definition(Some(t), Rc::new(id), None, Declared::default())),
+                    }
+                }
+
+                self.access(id)
+            },
+            TokenType::Keyword => match self.traveler.current_content().as_str() {
+                "fun" => {
+                    self.traveler.next();
+                    
+                    match self.traveler.current_content().as_str() {
+                        "(" => {
+                            self.traveler.next();
+
+                            let (param_names, param_types, param_defaults) = self.params()?;
+                            
+                            self.traveler.next();
+                            
+                            let t = self.returns()?;
+
+                            self.traveler.expect_content(":")?;
+                            self.traveler.next();
+                            
+                            let (t, body) = self.fun_body(t)?;
+
+                            Ok(Expression::Fun {
+                                param_names: Rc::new(param_names),
+                                param_types: Rc::new(param_types),
+                                param_defaults: Rc::new(param_defaults),
+                                t,
+                                body,
+                            })
+                        },
+                        
+                        _ => {
+                            let t = self.returns()?;
+
+                            self.traveler.expect_content(":")?;
+                            self.traveler.next();
+
+                            let (t, body) = self.fun_body(t)?;
+
+                            Ok(Expression::Fun {
+                                param_names: Rc::new(Vec::new()),
+                                param_types: Rc::new(Vec::new()),
+                                param_defaults: Rc::new(Vec::new()),
+                                t,
+                                body,
+                            })
+                        },
+                    }
+                },
+
+                "nil" => Ok(Expression::NilLiteral),
+
+                "throw" => {
+                    self.traveler.next();
+
+                    Ok(Expression::Throw(Rc::new(self.expression()?)))
+                },
+
+                "await" => {
+                    self.traveler.next();
+
+                    Ok(Expression::Await(Rc::new(self.term()?)))
+                },
+
+                "yield" => {
+                    let position = self.traveler.current().position;
+
+                    if self.yields.get().is_none() {
+                        return Err(ParserError::new_pos(position, "yield outside of a fun"))
+                    }
+
+                    self.yields.set(Some(true));
+                    self.traveler.next();
+
+                    Ok(Expression::Yield(Rc::new(self.expression()?)))
+                },
+
+                // super args calls the parent's constructor, super::m args its method
+                "super" => {
+                    let position = self.traveler.current().position;
+
+                    let parent = match *self.superclass.borrow() {
+                        Some(ref parent) => parent.clone(),
+                        None => return Err(ParserError::new_pos(position, "super outside of a class that extends another")),
+                    };
+
+                    self.traveler.next();
+
+                    let (method, args) = match self.traveler.current_content().as_str() {
+                        "::" => match self.method_call(Expression::Eof)? {
+                            Expression::MethodCall(_, method, args) => (Some(method), args),
+                            _ => unreachable!(),
+                        },
+                        "!" => (None, Rc::new(Vec::new())),
+                        _ => match self.call(Expression::Eof)? {
+                            Expression::Call(_, args) => (None, args),
+                            _ => return Err(ParserError::new_pos(position, "can't name args of super")),
+                        },
+                    };
+
+                    Ok(Expression::Super {
+                        parent,
+                        method,
+                        args,
+                    })
+                },
+
+                "if" => {
+                    let mut branches = vec![self.branch()?];
+                    let mut otherwise = None;
+
+                    loop {
+                        let checkpoint = self.traveler.checkpoint();
+
+                        self.traveler.next();
+                        self.skip_whitespace()?;
+
+                        match self.traveler.current_content().as_str() {
+                            "elif" => branches.push(self.branch()?),
+                            "else" => {
+                                self.traveler.next();
+                                self.traveler.expect_content(":")?;
+                                self.traveler.next();
+
+                                otherwise = Some(Rc::new(self.body()?));
+
+                                break
+                            },
+                            _ => {
+                                self.traveler.restore(checkpoint);
+
+                                break
+                            },
+                        }
+                    }
+
+                    Ok(Expression::If(Rc::new(branches), otherwise))
+                },
+
+                "match" => {
+                    self.traveler.next();
+
+                    let value = self.header()?;
+                    self.traveler.next();
+
+                    self.traveler.expect_content(":")?;
+                    self.traveler.next();
+
+                    self.traveler.expect_content("\n")?;
+                    self.traveler.next();
+
+                    let arms = match self.traveler.current().token_type.clone() {
+                        TokenType::Block(ref v) => self.sub(v.clone()).arms()?,
+                        _ => return Err(ParserError::new_pos(self.traveler.current().position, "expected match arms")),
+                    };
+
+                    Ok(Expression::Match(Rc::new(value), Rc::new(arms)))
+                },
+
+                _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected symbol: {}", self.traveler.current_content()))),
+            },
+            TokenType::Symbol => match self.traveler.current_content().as_str() {
+                "[" => {
+                    let position = self.traveler.current().position;
+                    self.traveler.next();
+                    
+                    let mut body = Vec::new();
+                    let mut keys = Vec::new(); // [hp: health] takes hp out of what's destructured
+
+                    while self.traveler.current_content() != "]" {
+                        self.skip_whitespace()?;
+
+                        let expr = self.expression()?;
+
+                        // the block ended before the bracket was closed
+                        if expr == Expression::Eof {
+                            return Err(ParserError::new_pos(position, "unclosed '['"))
+                        }
+
+                        self.skip_whitespace()?;
+                        
+                        self.traveler.next();
+
+                        match (expr, self.traveler.current_content().as_str()) {
+                            (Expression::Identifier(key), ":") => {
+                                self.traveler.next();
+
+                                body.push(Expression::Identifier(Rc::new(self.traveler.expect(TokenType::Identifier)?)));
+                                keys.push(key);
+
+                                self.traveler.next();
+                            },
+                            (expr, _) => body.push(expr),
+                        }
+
+                        if !keys.is_empty() && keys.len() != body.len() {
+                            return Err(ParserError::new_pos(position, "can't mix keyed and positional names"))
+                        }
+
+                        if self.traveler.current_content() == "," {
+                            self.traveler.next();
+                        }
+                        
+                        self.skip_whitespace()?;
+                    }
+
+                    self.traveler.next();
+
+                    if self.traveler.current_content() == "=" {
+                        let mut names = Vec::new();
+
+                        for e in body.iter() {
+                            match *e {
+                                Expression::Identifier(ref name) => names.push(name.clone()),
+                                _ => return Err(ParserError::new_pos(self.traveler.current().position, &format!("can't destructure into: {}", e))),
+                            }
+                        }
+
+                        self.traveler.next();
+
+                        return Ok(Expression::Destructure {
+                            names: Rc::new(names),
+                            value: Rc::new(self.expression()?),
+                            keys:  if keys.is_empty() { None } else { Some(Rc::new(keys)) },
+                            keyed: Rc::new(Cell::new(false)),
+                            declared: Declared::default(),
+                        })
+                    }
+
+                    if !keys.is_empty() {
+                        return Err(ParserError::new_pos(position, "expected '=' after keyed names"))
+                    }
+
+                    self.traveler.prev();
+                    
+                    Ok(Expression::DictLiteral(Rc::new(body)))
+                },
+                "(" => {
+                    self.traveler.next();
+                    let expr = self.expression()?;
+                    self.traveler.next();
+
+                    self.skip_whitespace()?;
+
+                    self.traveler.expect_content(")")?;
+
+                    self.traveler.next();
+
+                    // (get!).hp
+                    if self.traveler.current_content() == "." {
+                        return self.access(expr)
+                    }
+
+                    match self.traveler.current().token_type {
+                        TokenType::IntLiteral |
+                        TokenType::FloatLiteral |
+                        TokenType::BoolLiteral |
+                        TokenType::StringLiteral |
+                        TokenType::Identifier |
+                        TokenType::Symbol => {
+                            if self.traveler.current().token_type == TokenType::Symbol {
+                                match self.traveler.current_content().as_str() {
+                                    "!"  => {
+                                        self.traveler.next();
+                                        return Ok(Expression::Call(Rc::new(expr), Rc::new(vec!())));
+                                    },
+                                    "(" => (),
+                                    "=" => {
+                                        self.traveler.next();
+                                        let expr_right = self.expression()?;
+
+                                        return Ok(Expression::Definition(None, Rc::new(expr), Some(Rc::new(expr_right)), Declared::default()))
+                                    },
+                                    _   => {
+                                        self.traveler.prev();
+
+                                        return Ok(expr)
+                                    },
+                                }
+                            }
+
+                            let call = self.call(expr)?;
+
+                            self.traveler.next();
+
+                            return Ok(call)
+                        },
+
+                        _ => (),
+                    }
+
+                    self.traveler.prev();
+
+                    Ok(expr)
+                },
+
+                _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected symbol: {}", self.traveler.current_content()))),
+            },
+            _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected: {:#?}", self.traveler.current_content()))),
+        }
+    }
+    
+    // keys, calls and assignments after a name or a parenthesized term
+    fn access(&mut self, mut id: Expression) -> ParserResult<Expression> {
+        while self.traveler.current_content() == "." {
+            self.traveler.next();
+
+            // xs.(i + 1), the key is taken whole
+            if self.traveler.current_content() == "(" {
+                self.traveler.next();
+
+                let key = self.expression()?;
+                self.traveler.next();
+
+                self.skip_whitespace()?;
+                self.traveler.expect_content(")")?;
+                self.traveler.next();
+
+                id = Expression::Index(Rc::new(id), Rc::new(key), Rc::new(Cell::new(false)));
+                continue
+            }
+
+            match self.expression()? {
+                Expression::Definition(ref t, ref a, ref b, ref declared) => {
+                    id = Expression::Definition(t.clone(), Rc::new(index(id, a)), b.clone(), declared.clone());
+                    self.traveler.next();
+                },
+                Expression::Compound(ref a, ref op, ref b) => {
+                    id = Expression::Compound(Rc::new(index(id, a)), op.clone(), b.clone());
+                    self.traveler.next();
+                },
+                Expression::Call(ref a, ref args) => {
+                    id = Expression::Call(Rc::new(index(id, a)), args.clone());
+                    self.traveler.next();
+                },
+                Expression::MethodCall(ref a, ref method, ref args) => {
+                    id = Expression::MethodCall(Rc::new(index(id, a)), method.clone(), args.clone());
+                    self.traveler.next();
+                },
+                e => {
+                    id = index(id, &Rc::new(e));
+                    self.traveler.next();
+                },
+            }
+        }
+
+        match self.traveler.current().token_type {
+            TokenType::IntLiteral |
+            TokenType::FloatLiteral |
+            TokenType::BoolLiteral |
+            TokenType::StringLiteral |
+            TokenType::Identifier => {
+                let call = self.call(id)?;
+
+                Ok(call)
+            },
+
+            TokenType::Symbol => match self.traveler.current_content().as_str() {
+                // f(a, b), while `f (a)` passes a parenthesized arg
+                "(" if self.is_attached() => {
+                    self.traveler.next();
+
+                    let call = self.call(id)?;
+
+                    self.traveler.next();
+                    self.skip_whitespace()?;
+                    self.traveler.expect_content(")")?;
+
+                    Ok(call)
+                },
+                "(" => self.call(id),
+                ")" | "," | "]" | "|>" | ":" => {
+                    self.traveler.prev();
+
+                    Ok(id)
+                },
+                "!"       => Ok(Expression::Call(Rc::new(id), Rc::new(vec!()))),
+                "::"      => self.method_call(id),
+                "="       => {                            
+                    self.traveler.next();
+                    let expr = self.expression()?;
+
+                    Ok(Expression::Definition(None, Rc::new(id), Some(Rc::new(expr)), Declared::default()))
+                },
+                "+=" | "-=" | "*=" | "/=" | "%=" | "^=" | "++=" => {
+                    let content = self.traveler.current_content();
+                    let (op, _) = get_operand(&content[.. content.len() - 1]).unwrap();
+
+                    self.traveler.next();
+                    let expr = self.expression()?;
+
+                    Ok(Expression::Compound(Rc::new(id), op, Rc::new(expr)))
+                },
+
+                _   => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected: {}", self.traveler.current_content()))),
+            },
+            _ => {
+                self.traveler.prev();
+
+                Ok(id)
+            },
+        }
+    }
+
+    fn block(&mut self) -> ParserResult<Vec<Statement>> {        
+        match self.traveler.current().token_type.clone() {
+            TokenType::Block(ref v) => {
+                let mut p = self.sub(v.clone());
+                Ok(p.statements())
+            },
+            _ => Ok(vec![Statement::Expression(Rc::new(self.expression()?))]),
+        }
+    }
+
+    // if or elif, then condition: body
+    fn branch(&mut self) -> ParserResult<Branch> {
+        self.traveler.next();
+
+        let condition = self.header()?;
+        self.traveler.next();
+
+        self.traveler.expect_content(":")?;
+        self.traveler.next();
+
+        Ok(Branch {
+            condition: Rc::new(condition),
+            body:      Rc::new(self.body()?),
+        })
+    }
+
+    // the expression before a header's ':', where `name:` ends the header
+    fn header(&mut self) -> ParserResult<Expression> {
+        let outer   = self.header;
+        self.header = true;
+
+        let expr = self.expression();
+
+        self.header = outer;
+
+        expr
+    }
+
+    // an indented block, or an expression on the same line
+    fn body(&mut self) -> ParserResult<Vec<Statement>> {
+        if self.traveler.current_content() == "\n" {
+            self.traveler.next();
+
+            self.block()
+        } else if self.traveler.current_content() == "return" {
+            Ok(self.statement()?.into_iter().collect())
+        } else {
+            Ok(vec![Statement::Expression(Rc::new(self.expression()?))])
+        }
+    }
+
+    // pattern [if guard]: body
+    fn arms(&mut self) -> ParserResult<Vec<Arm>> {
+        let mut arms = Vec::new();
+
+        while self.traveler.remaining() > 2 {
+            self.skip_whitespace()?;
+
+            if self.traveler.current().token_type == TokenType::Eof {
+                break
+            }
+
+            let pattern = self.pattern()?;
+
+            let guard = if self.traveler.current_content() == "if" {
+                self.traveler.next();
+
+                let guard = self.expression()?;
+                self.traveler.next();
+
+                Some(Rc::new(guard))
+            } else {
+                None
+            };
+
+            self.traveler.expect_content(":")?;
+            self.traveler.next();
+
+            let body = self.body()?;
+
+            self.traveler.next();
+
+            arms.push(Arm {
+                pattern,
+                guard,
+                body: Rc::new(body),
+            })
+        }
+
+        Ok(arms)
+    }
+
+    fn pattern(&mut self) -> ParserResult<Pattern> {
+        let position = self.traveler.current().position;
+
+        let pattern = match self.traveler.current().token_type {
+            TokenType::IntLiteral    |
+            TokenType::FloatLiteral  |
+            TokenType::BoolLiteral   |
+            TokenType::StringLiteral => Pattern::Literal(self.term()?),
+
+            TokenType::Identifier => {
+                let mut name = self.traveler.current_content();
+                let mut enum_name = None;
+
+                self.traveler.next();
+
+                // Shape.Circle
+                if self.traveler.current_content() == "." {
+                    self.traveler.next();
+
+                    enum_name = Some(name);
+                    name = self.traveler.expect(TokenType::Identifier)?;
+
+                    self.traveler.next();
+                }
+
+                let t = self.variant(enum_name.as_deref(), &name);
+
+                if let (Some(enum_name), None) = (&enum_name, &t) {
+                    return Err(ParserError::new_pos(position, &format!("{}.{}: unknown variant", enum_name, name)))
+                }
+
+                match t {
+                    Some(t) => {
+                        let mut content = Vec::new();
+
+                        if self.traveler.current_content() == "(" {
+                            self.traveler.next();
+
+                            while self.traveler.current_content() != ")" {
+                                content.push(self.pattern()?);
+
+                                if self.traveler.current_content() == "," {
+                                    self.traveler.next();
+                                }
+                            }
+                        } else {
+                            self.traveler.prev();
+                        }
+
+                        Pattern::Variant(t, Rc::new(name), Rc::new(content))
+                    },
+
+                    None => {
+                        self.traveler.prev();
+
+                        match name.as_str() {
+                            "_" => Pattern::Wildcard,
+                            _   => Pattern::Binding(Rc::new(name)),
+                        }
+                    },
+                }
+            },
+
+            TokenType::Keyword if self.traveler.current_content() == "nil" => Pattern::Literal(Expression::NilLiteral),
+
+            TokenType::Operator if self.traveler.current_content() == "-" => {
+                self.traveler.next();
+
+                match self.term()? {
+                    Expression::NumberLiteral(n) => Pattern::Literal(Expression::NumberLiteral(-n)),
+                    e => return Err(ParserError::new_pos(position, &format!("can't match against: -{}", e))),
+                }
+            },
+
+            TokenType::Symbol if self.traveler.current_content() == "[" => {
+                self.traveler.next();
+
+                let mut content = Vec::new();
+                let mut keyed   = Vec::new();
+
+                while self.traveler.current_content() != "]" {
+                    let key = if self.traveler.current().token_type == TokenType::Identifier {
+                        let name = Rc::new(self.traveler.current_content());
+                        self.traveler.next();
+
+                        if self.traveler.current_content() == ":" {
+                            self.traveler.next();
+                            Some(name)
+                        } else {
+                            self.traveler.prev();
+                            None
+                        }
+                    } else {
+                        None
+                    };
+
+                    let pattern = self.pattern()?;
+
+                    match key {
+                        Some(key) => keyed.push((key, pattern)),
+                        None      => content.push(pattern),
+                    }
+
+                    if !keyed.is_empty() && !content.is_empty() {
+                        return Err(ParserError::new_pos(position, "can't mix keyed and positional patterns"))
+                    }
+
+                    if self.traveler.current_content() == "," {
+                        self.traveler.next();
+                    }
+                }
+
+                if keyed.is_empty() {
+                    Pattern::Array(Rc::new(content))
+                } else {
+                    Pattern::Table(Rc::new(keyed))
+                }
+            },
+
+            _ => return Err(ParserError::new_pos(position, &format!("unexpected pattern: {}", self.traveler.current_content()))),
+        };
+
+        self.traveler.next();
+
+        Ok(pattern)
+    }
+
+    fn params(&mut self) -> ParserResult<Params> {
+        let mut param_names    = Vec::new();
+        let mut param_types    = Vec::new();
+        let mut param_defaults = Vec::new();
+
+        let mut variadic = false;
+
+        while self.traveler.current_content() != ")" {
+            let position = self.traveler.current().position;
+
+            param_names.push(Rc::new(self.traveler.expect(TokenType::Identifier)?));
+            self.traveler.next();
+
+            match self.types()? {
+                Some(t) => param_types.push(t),
+                None if self.traveler.current().token_type == TokenType::Identifier => return Err(self.unknown_type()),
+                None    => param_types.push(Type::Any),
+            }
+
+            // spelled out, not through an alias
+            variadic = self.traveler.checkpoint() > 0 && self.traveler.tokens[self.traveler.checkpoint() - 1].content() == "..";
+
+            // a trailing `t..` may be left empty, so needs no default
+            let rest = variadic && self.traveler.current_content() == ")";
+
+            if self.traveler.current_content() == "=" {
+                self.traveler.next();
+
+                param_defaults.push(Some(Rc::new(self.expression()?)));
+                self.traveler.next();
+            } else if param_defaults.iter().any(|d| d.is_some()) && !rest {
+                return Err(ParserError::new_pos(position, &format!("expected default for: {}", param_names.last().unwrap())))
+            } else {
+                param_defaults.push(None)
+            }
+
+            if self.traveler.current_content() == "," {
+                self.traveler.next();
+            }
+        }
+
+        // a trailing `t..` takes the rest of the args
+        match param_types.pop() {
+            Some(Type::Array(ref t)) if variadic => param_types.push(Type::Many(t.clone())),
+            Some(t)              => param_types.push(t),
+            None                 => (),
+        }
+
+        Ok((param_names, param_types, param_defaults))
+    }
+
+    fn call(&mut self, caller: Expression) -> ParserResult<Expression> {
+        let mut args  = Vec::new();
+        let mut named = Vec::new();
+
+        loop {
+            match self.traveler.current().token_type {
+                TokenType::Block(_) | TokenType::Eol | TokenType::Eof => break,
+                _ => match self.traveler.current_content().as_str() {
+                    ")" | "\n" | "|>" | ":" => break,
+                    _ => (),
+                },
+            }
+
+            let position = self.traveler.current().position;
+            let start    = self.traveler.checkpoint();
+
+            match self.named_arg()? {
+                Some(arg) => named.push(arg),
+                None      => match self.value()? {
+                    Expression::Definition(None, ref id, Some(ref value), _) if matches!(**id, Expression::Identifier(_)) => {
+                        named.push((Rc::new(format!("{}", id)), (**value).clone()))
+                    },
+                    e => if named.is_empty() {
+                        args.push(e)
+                    } else {
+                        return Err(ParserError::new_pos(position, "positional arg after named args"))
+                    },
+                },
+            }
+
+            self.traveler.next();
+
+            if self.traveler.current_content() == "," {
+                self.traveler.next();
+            } else if self.traveler.checkpoint() <= start {
+                break
+            }
+        }
+        
+        // leave the closing token to whoever opened it
+        self.traveler.prev();
+
+        if named.is_empty() {
+            Ok(Expression::Call(Rc::new(caller), Rc::new(args)))
+        } else {
+            Ok(Expression::NamedCall {
+                id:    Rc::new(caller),
+                args:  Rc::new(args),
+                named: Rc::new(named),
+                order: Rc::new(RefCell::new(Vec::new())),
+            })
+        }
+    }
+
+    // receiver::method args, the receiver is passed as self
+    fn method_call(&mut self, receiver: Expression) -> ParserResult<Expression> {
+        self.traveler.next();
+
+        let method = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+        self.traveler.next();
+
+        if self.traveler.current_content() == "!" {
+            return Ok(Expression::MethodCall(Rc::new(receiver), method, Rc::new(Vec::new())))
+        }
+
+        let position = self.traveler.current().position;
+
+        // o::move(a, b), as f(a, b)
+        let parenthesized = self.traveler.current_content() == "(" && self.is_attached();
+
+        if parenthesized {
+            self.traveler.next();
+        }
+
+        let call = self.call(Expression::Eof)?;
+
+        if parenthesized {
+            self.traveler.next();
+            self.skip_whitespace()?;
+            self.traveler.expect_content(")")?;
+        }
+
+        match call {
+            Expression::Call(_, args) => Ok(Expression::MethodCall(Rc::new(receiver), method, args)),
+            _ => Err(ParserError::new_pos(position, &format!("{}: can't name args of methods", method))),
+        }
+    }
+
+    // name: value, with the value on the same line
+    fn named_arg(&mut self) -> ParserResult<Option<(Rc<String>, Expression)>> {
+        if self.header || self.traveler.current().token_type != TokenType::Identifier {
+            return Ok(None)
+        }
+
+        let name = Rc::new(self.traveler.current_content());
+        self.traveler.next();
+
+        if self.traveler.current_content() == ":" {
+            self.traveler.next();
+
+            if !self.is_newline() && !matches!(self.traveler.current().token_type, TokenType::Block(_) | TokenType::Eof) {
+                return Ok(Some((name, self.value()?)))
+            }
+
+            self.traveler.prev();
+        }
+
+        self.traveler.prev();
+        Ok(None)
+    }
+
+    // num | str
+    fn types(&mut self) -> ParserResult<Option<Type>> {
+        let first = match self.single_type()? {
+            Some(t) => t,
+            None    => return Ok(None),
+        };
+
+        let mut members = vec![first];
+
+        while self.traveler.current_content() == "|" {
+            self.traveler.next();
+
+            let position = self.traveler.current().position;
+
+            match self.single_type()? {
+                Some(t) => members.push(t),
+                None    => return Err(ParserError::new_pos(position, &format!("expected type after '|', found: {}", self.traveler.current_content()))),
+            }
+        }
+
+        if members.len() == 1 {
+            return Ok(members.pop())
+        }
+
+        Ok(Some(Type::union(members)))
+    }
+
+    // (num, str), only where a fun's return type goes
+    fn returns(&mut self) -> ParserResult<Option<Type>> {
+        if self.traveler.current_content() != "(" {
+            return self.types()
+        }
+
+        self.traveler.next();
+
+        let mut types = Vec::new();
+
+        while self.traveler.current_content() != ")" {
+            let position = self.traveler.current().position;
+
+            match self.types()? {
+                Some(t) => types.push(t),
+                None    => return Err(ParserError::new_pos(position, &format!("expected return type, found: {}", self.traveler.current_content()))),
+            }
+
+            if self.traveler.current_content() == "," {
+                self.traveler.next();
+            }
+        }
+
+        self.traveler.next();
+
+        Ok(Some(Type::tuple(types)))
+    }
+
+    fn single_type(&mut self) -> ParserResult<Option<Type>> {
+        let t = match self.traveler.current().token_type {
+            TokenType::Type       => get_type(&self.traveler.current_content()).unwrap(),
+            // num? lexes as one name
+            TokenType::Identifier if self.traveler.current_content().ends_with('?') => {
+                let content = self.traveler.current_content();
+                let name    = &content[.. content.len() - 1];
+
+                let t = match get_type(name) {
+                    Some(t) => t,
+                    None    => match self.types.borrow().get(name) {
+                        Some(t) => t.clone(),
+                        None    => return Ok(None),
+                    },
+                };
+
+                t.optional()
+            },
+            TokenType::Identifier => {
+                let mut name = self.traveler.current_content();
+
+                // button.Btn, exported by an imported module
+                if !self.types.borrow().contains_key(&name) && self.traveler.remaining() > 3 {
+                    let top = self.traveler.checkpoint();
+
+                    if self.traveler.get(top + 1).content() == "." {
+                        let qualified = format!("{}.{}", name, self.traveler.get(top + 2).content());
+
+                        if self.types.borrow().contains_key(&qualified) {
+                            self.traveler.next();
+                            self.traveler.next();
+
+                            name = qualified
+                        }
+                    }
+                }
+
+                let t = match self.types.borrow().get(&name) {
+                    Some(t) => t.clone(),
+                    None    => return Ok(None),
+                };
+
+                let generics = self.generics.borrow().get(&name).cloned();
+
+                match generics {
+                    // Box<num>, missing params are any
+                    Some(generics) => {
+                        let mut args = HashMap::new();
+
+                        self.traveler.next();
+
+                        if self.traveler.current_content() == "<" {
+                            self.traveler.next();
+
+                            for g in generics.iter() {
+                                let position = self.traveler.current().position;
+
+                                match self.types()? {
+                                    Some(t) => args.insert(g.to_string(), t),
+                                    None    => return Err(ParserError::new_pos(position, &format!("{}: expected type for '{}'", name, g))),
+                                };
+
+                                if self.traveler.current_content() == "," {
+                                    self.traveler.next();
+                                }
+                            }
+
+                            self.traveler.expect_content(">")?;
+                        } else {
+                            self.traveler.prev();
+                        }
+
+                        t.substitute(&args)
+                    },
+
+                    None => t,
+                }
+            },
+            // {str: num}
+            TokenType::Symbol if self.traveler.current_content() == "{" => {
+                self.traveler.next();
+
+                let position = self.traveler.current().position;
+
+                let key = match self.types()? {
+                    Some(t) => t,
+                    None    => return Err(ParserError::new_pos(position, &format!("expected key type, found: {}", self.traveler.current_content()))),
+                };
+
+                self.traveler.expect_content(":")?;
+                self.traveler.next();
+
+                let position = self.traveler.current().position;
+
+                let value = match self.types()? {
+                    Some(t) => t,
+                    None    => return Err(ParserError::new_pos(position, &format!("expected value type, found: {}", self.traveler.current_content()))),
+                };
+
+                self.traveler.expect_content("}")?;
+
+                Type::Map(Rc::new(key), Rc::new(value))
+            },
+            // fun(num, str) bool
+            TokenType::Keyword if self.traveler.current_content() == "fun" => {
+                self.traveler.next();
+                self.traveler.expect_content("(")?;
+                self.traveler.next();
+
+                let mut params = Vec::new();
+
+                while self.traveler.current_content() != ")" {
+                    let position = self.traveler.current().position;
+
+                    match self.types()? {
+                        Some(t) => params.push(t),
+                        None    => return Err(ParserError::new_pos(position, &format!("expected param type, found: {}", self.traveler.current_content()))),
+                    }
+
+                    if self.traveler.current_content() == "," {
+                        self.traveler.next();
+                    }
+                }
+
+                self.traveler.next();
+
+                let mut tp = vec![self.returns()?.unwrap_or(Type::Any)];
+                tp.extend(params.iter().cloned());
+
+                return Ok(Some(Type::Fun(Rc::new(tp), params.len())))
+            },
+            _ => return Ok(None),
+        };
+
+        self.traveler.next();
+
+        match self.traveler.current_content().as_str() {
+            ".." => {
+                self.traveler.next();
+
+                // num..?
+                if self.traveler.current_content() == "?" {
+                    self.traveler.next();
+
+                    return Ok(Some(Type::Array(Rc::new(t)).optional()))
+                }
+
+                Ok(Some(Type::Array(Rc::new(t))))
+            },
+
+            _ => Ok(Some(t))
+        }
+    }
+
+    // a type after a name, unless it's a declared type passed as the first arg
+    fn annotation(&mut self) -> ParserResult<Option<Type>> {
+        let checkpoint = self.traveler.checkpoint();
+        let named      = self.traveler.current().token_type == TokenType::Identifier;
+
+        let t = self.types()?;
+
+        // x Vec3 = 1, while spawn y = 2 names an arg
+        let unknown = named && t.is_none() && self.traveler.current().token_type == TokenType::Identifier
+            && self.traveler.current_content().starts_with(char::is_uppercase)
+            && self.traveler.remaining() > 1 && self.traveler.get(checkpoint + 1).content() == "=";
+
+        if unknown {
+            return Err(self.unknown_type())
+        }
+
+        if named && t.is_some() {
+            match self.traveler.current().token_type {
+                TokenType::IntLiteral    |
+                TokenType::FloatLiteral  |
+                TokenType::BoolLiteral   |
+                TokenType::StringLiteral |
+                TokenType::Identifier    => {
+                    self.traveler.restore(checkpoint);
+
+                    return Ok(None)
+                },
+                // area Shape.Circle 2
+                TokenType::Symbol if self.traveler.current_content() == "." => {
+                    self.traveler.restore(checkpoint);
+
+                    return Ok(None)
+                },
+                _ => (),
+            }
+        }
+
+        Ok(t)
+    }
+
+    // a name where only a type can go
+    fn unknown_type(&self) -> ParserError {
+        ParserError::new_pos(self.traveler.current().position, &format!("unknown type: {}", self.traveler.current_content()))
+    }
+
+    // type Name = or type Name<
+    fn is_alias(&self) -> bool {
+        let top = self.traveler.checkpoint();
+
+        match (self.traveler.tokens.get(top + 1), self.traveler.tokens.get(top + 2)) {
+            (Some(name), Some(next)) => (name.token_type == TokenType::Identifier || name.token_type == TokenType::Type) && (next.content() == "=" || next.content() == "<"),
+            _ => false,
+        }
+    }
+
+    fn declare_type(&self, position: TokenPosition, name: &str, t: Type) -> ParserResult<()> {
+        let mut types = self.types.borrow_mut();
+
+        if types.contains_key(name) || get_type(name).is_some() {
+            return Err(ParserError::new_pos(position, &format!("{}: type already declared", name)))
+        }
+
+        types.insert(name.to_string(), t);
+
+        Ok(())
+    }
+
+    // <T, U>
+    fn generics(&mut self) -> ParserResult<Vec<Rc<String>>> {
+        let mut generics = Vec::new();
+
+        if self.traveler.current_content() != "<" {
+            return Ok(generics)
+        }
+
+        self.traveler.next();
+
+        while self.traveler.current_content() != ">" {
+            generics.push(Rc::new(self.traveler.expect(TokenType::Identifier)?));
+            self.traveler.next();
+
+            if self.traveler.current_content() == "," {
+                self.traveler.next();
+            }
+        }
+
+        self.traveler.next();
+
+        Ok(generics)
+    }
+
+    // makes type params known as types, returning what they shadow
+    fn bind_generics(&self, generics: &[Rc<String>]) -> Vec<(String, Option<Type>)> {
+        let mut types = self.types.borrow_mut();
+
+        generics.iter().map(|g| (g.to_string(), types.insert(g.to_string(), Type::Var(g.clone())))).collect()
+    }
+
+    fn unbind_generics(&self, shadowed: Vec<(String, Option<Type>)>) {
+        let mut types = self.types.borrow_mut();
+
+        for (name, t) in shadowed {
+            match t {
+                Some(t) => types.insert(name, t),
+                None    => types.remove(&name),
+            };
+        }
+    }
+
+    // Variant(name type, ...), ... like fields
+    fn variants(&mut self, block: bool) -> ParserResult<Vec<(Rc<String>, Fields)>> {
+        let mut variants: Vec<(Rc<String>, Fields)> = Vec::new();
+
+        loop {
+            if block {
+                self.skip_whitespace()?;
+            }
+
+            if self.traveler.remaining() < 2 || self.traveler.current().token_type == TokenType::Eof || self.traveler.current_content() == "\n" {
+                break
+            }
+
+            let position = self.traveler.current().position;
+
+            let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+            self.traveler.next();
+
+            if variants.iter().any(|(n, _)| *n == name) {
+                return Err(ParserError::new_pos(position, &format!("{}: variant declared twice", name)))
+            }
+
+            let fields = if self.traveler.current_content() == "(" {
+                self.traveler.next();
+
+                let fields = self.fields(false)?;
+
+                self.traveler.expect_content(")")?;
+                self.traveler.next();
+
+                fields
+            } else {
+                Vec::new()
+            };
+
+            variants.push((name, Rc::new(fields)));
+
+            if self.traveler.current_content() == "," {
+                self.traveler.next();
+            }
+        }
+
+        Ok(variants)
+    }
+
+    // the enum declaring a variant, optionally only looking in one enum
+    fn variant(&self, enum_name: Option<&str>, name: &str) -> Option<Type> {
+        self.types.borrow().iter().filter(|&(n, _)| enum_name.is_none() || enum_name == Some(n.as_str())).map(|(_, t)| t).find(|t| match **t {
+            Type::Enum(_, ref variants) => variants.iter().any(|(v, _)| v.as_str() == name),
+            _ => false,
+        }).cloned()
+    }
+
+    // name type, ... on one line, in parens or one per line in a block
+    fn fields(&mut self, block: bool) -> ParserResult<Vec<(Rc<String>, Type)>> {
+        let mut fields: Vec<(Rc<String>, Type)> = Vec::new();
+
+        loop {
+            if block {
+                self.skip_whitespace()?;
+            }
+
+            if self.traveler.remaining() < 2 || self.traveler.current().token_type == TokenType::Eof || self.traveler.current_content() == "\n" || self.traveler.current_content() == ")" {
+                break
+            }
+
+            let position = self.traveler.current().position;
+
+            let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+            self.traveler.next();
+
+            if fields.iter().any(|(n, _)| *n == name) {
+                return Err(ParserError::new_pos(position, &format!("{}: field declared twice", name)))
+            }
 
-            _ => (),
+            match self.types()? {
+                Some(t) => fields.push((name, t)),
+                None    => return Err(ParserError::new_pos(position, &format!("{}: expected field type", name))),
+            }
+
+            if self.traveler.current_content() == "," {
+                self.traveler.next();
+            }
         }
 
-        match self.traveler.current().token_type {
-            TokenType::IntLiteral    => Ok(Expression::NumberLiteral(self.traveler.current_content().parse::<f64>().unwrap())),
-            TokenType::FloatLiteral  => Ok(Expression::NumberLiteral(self.traveler.current_content().parse::<f64>().unwrap())),
-            TokenType::BoolLiteral   => Ok(Expression::BoolLiteral(self.traveler.current_content() == "true")),
-            TokenType::StringLiteral => Ok(Expression::StringLiteral(Rc::new(self.traveler.current_content().clone()))),
-            TokenType::Identifier    => {
-                let mut id = Expression::Identifier(Rc::new(self.traveler.current_content()));
+        Ok(fields)
+    }
 
-                self.traveler.next();
+    // a fun's body, a fun that yields is a generator of its return type
+    fn fun_body(&mut self, t: Option<Type>) -> ParserResult<(Option<Type>, Rc<Vec<Statement>>)> {
+        let outer   = self.yields.replace(Some(false));
+        let body    = self.body();
+        let yielded = self.yields.replace(outer) == Some(true);
 
-                if let Some(t) = self.types()? {                    
-                    match self.traveler.current_content().as_str() {
-                        "=" => {
-                            self.traveler.next();
-                            let expr = self.expression()?;
-                            
-                            return Ok(Expression::Definition(Some(t), Rc::new(id), Some(Rc::new(expr))))
-                        },
+        let t = if yielded {
+            Some(Type::Iter(Rc::new(t.unwrap_or(Type::Undefined))))
+        } else {
+            t
+        };
 
-                        _ => return Ok(Expression::Definition(Some(t), Rc::new(id), None)),
-                    }
-                } else if self.traveler.current_content() == "." {
-                    self.traveler.next();
+        Ok((t, Rc::new(body?)))
+    }
 
-                    match self.expression()? {
-                        Expression::Definition(ref t, ref a, ref b) => {
-                            id = Expression::Definition(t.clone(), Rc::new(Expression::Index(Rc::new(id), a.clone())), b.clone());
-                            self.traveler.next();
-                        },
-                        Expression::Call(ref a, ref args) => {
-                            id = Expression::Call(Rc::new(Expression::Index(Rc::new(id), a.clone())), args.clone());
-                            self.traveler.next();
-                        },
-                        e => {
-                            id = Expression::Index(Rc::new(id), Rc::new(e));
-                            self.traveler.next();
-                        },
-                    }
-                }
+    // a fun after its name, with its params, return type and body
+    fn fun_rest(&mut self, name: Rc<String>) -> ParserResult<Statement> {
+        // type params are only types inside the fun
+        let generics = self.generics()?;
+        let shadowed = self.bind_generics(&generics);
 
-                match self.traveler.current().token_type {
-                    TokenType::IntLiteral |
-                    TokenType::FloatLiteral |
-                    TokenType::BoolLiteral |
-                    TokenType::StringLiteral |
-                    TokenType::Identifier => {
-                        let call = self.call(id)?;
+        let fun = match self.traveler.current_content().as_str() {
+            "(" => {
+                self.traveler.next();
 
-                        Ok(call)
-                    },
+                let (param_names, param_types, param_defaults) = self.params()?;
 
-                    TokenType::Symbol => match self.traveler.current_content().as_str() {
-                        "(" | ")" | "," => {
-                            self.traveler.prev();
+                self.traveler.next();
 
-                            Ok(id)
-                        },
-                        "!"       => Ok(Expression::Call(Rc::new(id), Rc::new(vec!()))),
-                        "="       => {                            
-                            self.traveler.next();
-                            let expr = self.expression()?;
+                let t = self.returns()?;
 
-                            Ok(Expression::Definition(None, Rc::new(id), Some(Rc::new(expr))))
-                        },
+                self.traveler.expect_content(":")?;
+                self.traveler.next();
 
-                        _   => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected: {}", self.traveler.current_content()))),
-                    },
-                    _ => {
-                        self.traveler.prev();
+                let (t, body) = self.fun_body(t)?;
 
-                        Ok(id)
-                    },
-                }
+                Ok(Statement::Fun {
+                    name,
+                    param_names: Rc::new(param_names),
+                    param_types: Rc::new(param_types),
+                    param_defaults: Rc::new(param_defaults),
+                    t,
+                    body,
+                })
             },
-            TokenType::Keyword => match self.traveler.current_content().as_str() {
-                "fun" => {
-                    self.traveler.next();
-                    
-                    match self.traveler.current_content().as_str() {
-                        "(" => {
-                            self.traveler.next();
 
-                            let mut param_names = Vec::new();
-                            let mut param_types = Vec::new();
+            _ => {
+                let t = self.returns()?;
 
-                            while self.traveler.current_content() != ")" {
-                                param_names.push(Rc::new(self.traveler.expect(TokenType::Identifier)?));
-                                self.traveler.next();
+                self.traveler.expect_content(":")?;
+                self.traveler.next();
 
-                                if self.traveler.current().token_type == TokenType::Type {
-                                    param_types.push(get_type(&self.traveler.expect(TokenType::Type)?).unwrap());
-                                    self.traveler.next();
-                                } else {
-                                    param_types.push(Type::Any);
-                                }
+                let (t, body) = self.fun_body(t)?;
 
-                                if self.traveler.current_content() == "," {
-                                    self.traveler.next();
-                                }
-                            }
-                            
-                            self.traveler.next();
-                            
-                            let mut t = None;
-                            
-                            if self.traveler.current().token_type == TokenType::Type {
-                                t = get_type(&self.traveler.current_content());
-                                self.traveler.next();
-                            }
+                Ok(Statement::Fun {
+                    name,
+                    param_names: Rc::new(Vec::new()),
+                    param_types: Rc::new(Vec::new()),
+                    param_defaults: Rc::new(Vec::new()),
+                    t,
+                    body,
+                })
+            },
+        };
 
-                            self.traveler.expect_content(":")?;
-                            self.traveler.next();
-                            
-                            let body;
-                            
-                            if self.traveler.current_content() == "\n" {
-                                self.traveler.next();
-                                
-                                body = Rc::new(self.block()?);
-                            } else {
-                                
-                                body = Rc::new(vec![Statement::Expression(Rc::new(self.expression()?))])
-                            }
+        self.unbind_generics(shadowed);
 
-                            Ok(Expression::Fun {
-                                param_names: Rc::new(param_names),
-                                param_types: Rc::new(param_types),
-                                t,
-                                body,
-                            })
-                        },
-                        
-                        _ => {
-                            let mut t = None;
+        fun
+    }
 
-                            if self.traveler.current().token_type == TokenType::Type {
-                                t = get_type(&self.traveler.current_content());
-                                self.traveler.next();
-                            }
+    // fields, methods and operators of a class or record, registering it again after each
+    fn members<F>(&mut self, owner: &Rc<String>, with_methods: bool, fields: &mut Vec<(Rc<String>, Type)>, methods: &mut Vec<(Rc<String>, Type)>, declared: F) -> ParserResult<Vec<Statement>>
+        where F: Fn(&[(Rc<String>, Type)], &[(Rc<String>, Type)]) -> Type {
+        let mut funs = Vec::new();
+        let mut names: Vec<Rc<String>> = Vec::new();
 
-                            self.traveler.expect_content(":")?;
-                            self.traveler.next();
+        loop {
+            self.skip_whitespace()?;
 
-                            let body;
+            if self.traveler.remaining() < 2 || self.traveler.current().token_type == TokenType::Eof {
+                break
+            }
 
-                            if self.traveler.current_content() == "\n" {
-                                self.traveler.next();
-                                
-                                body = Rc::new(self.block()?);
-                            } else {
-                                body = Rc::new(vec![Statement::Expression(Rc::new(self.expression()?))])
-                            }
+            let position = self.traveler.current().position;
 
-                            Ok(Expression::Fun {
-                                param_names: Rc::new(Vec::new()),
-                                param_types: Rc::new(Vec::new()),
-                                t,
-                                body,
-                            })
-                        },
-                    }
+            let fun = match self.traveler.current_content().as_str() {
+                "fun" | "async" if with_methods => match self.statement()? {
+                    Some(fun @ Statement::Fun { .. }) => Some(fun),
+                    _ => return Err(ParserError::new_pos(position, &format!("{}: expected method", owner))),
                 },
 
-                _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected symbol: {}", self.traveler.current_content()))),
-            },
-            TokenType::Symbol => match self.traveler.current_content().as_str() {
-                "[" => {
+                "fun" => return Err(ParserError::new_pos(position, &format!("{}: records only have operators, not methods", owner))),
+
+                // op + (other Vec2) Vec2: ..., an op field is just a field
+                "op" if self.traveler.tokens.get(self.traveler.checkpoint() + 1).is_some_and(|t| t.token_type != TokenType::Identifier && t.token_type != TokenType::Type) => {
                     self.traveler.next();
-                    
-                    let mut body = Vec::new();
 
-                    while self.traveler.current_content() != "]" {
-                        self.skip_whitespace()?;
+                    let symbol = self.traveler.current_content();
 
-                        let expr = self.expression()?;
+                    if symbol == "[" {
+                        self.traveler.next();
+                        self.traveler.expect_content("]")?;
+                    }
 
-                        if expr == Expression::EOF {
-                            println!("eow");
-                            while self.traveler.current_content() != "]" {
-                                println!("hey: {}", self.traveler.current_content());
-                                self.traveler.prev();
-                            }
-                            break
-                        }
+                    let name = match metamethod(&symbol) {
+                        Some(name) => Rc::new(name.to_string()),
+                        None       => return Err(ParserError::new_pos(position, &format!("{}: can't implement op {}", owner, symbol))),
+                    };
 
-                        body.push(expr);
+                    self.traveler.next();
 
-                        self.skip_whitespace()?;
-                        
-                        self.traveler.next();
-                        println!("next here: {}", self.traveler.current_content());
+                    let op = self.fun_rest(name)?;
 
-                        if self.traveler.current_content() == "," {
-                            self.traveler.next();
+                    if let Statement::Fun { ref param_names, .. } = op {
+                        if param_names.len() != 1 {
+                            return Err(ParserError::new_pos(position, &format!("{}: op {} takes one param besides self", owner, symbol)))
                         }
-                        
-                        self.skip_whitespace()?;
-                        println!("next white: {}", self.traveler.current_content())
                     }
-                    
-                    println!("body: {:#?}", body);
-
-                    println!("nextest: {}", self.traveler.current_content());
-
-                    self.traveler.next();
 
-                    println!("nextest2: {:?}", self.traveler.current().token_type);
-                    
-                    Ok(Expression::DictLiteral(Rc::new(body)))
+                    Some(op)
                 },
-                "(" => {
-                    self.traveler.next();
-                    let expr = self.expression()?;
-                    self.traveler.next();
-
-                    self.skip_whitespace()?;
 
-                    self.traveler.expect_content(")")?;
+                _ => None,
+            };
 
+            match fun {
+                Some(fun) => {
                     self.traveler.next();
 
-                    match self.traveler.current().token_type {
-                        TokenType::IntLiteral |
-                        TokenType::FloatLiteral |
-                        TokenType::BoolLiteral |
-                        TokenType::StringLiteral |
-                        TokenType::Identifier |
-                        TokenType::Symbol => {
-                            if self.traveler.current().token_type == TokenType::Symbol {
-                                match self.traveler.current_content().as_str() {
-                                    "!"  => {
-                                        self.traveler.next();
-                                        return Ok(Expression::Call(Rc::new(expr), Rc::new(vec!())));
-                                    },
-                                    "(" => (),
-                                    "=" => {
-                                        self.traveler.next();
-                                        let expr_right = self.expression()?;
-
-                                        return Ok(Expression::Definition(None, Rc::new(expr), Some(Rc::new(expr_right))))
-                                    },
-                                    _   => return Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected: {}", self.traveler.current_content()))),
-                                }
-                            }
-
-                            let call = self.call(expr)?;
+                    let (name, t) = fun.method_signature().unwrap();
 
-                            self.traveler.next();
+                    if names.contains(&name) {
+                        return Err(ParserError::new_pos(position, &format!("{}: declared twice in '{}'", name, owner)))
+                    }
 
-                            return Ok(call)
-                        },
+                    names.push(name.clone());
 
-                        _ => (),
+                    if name.as_str() == "new" && is_async(match fun { Statement::Fun { ref t, .. } => t, _ => &None }) {
+                        return Err(ParserError::new_pos(position, &format!("{}: the constructor can't be async", owner)))
                     }
 
-                    self.traveler.prev();
+                    // the constructor isn't called on instances
+                    if name.as_str() != "new" {
+                        methods.retain(|(n, _)| *n != name);
+                        methods.push((name, t))
+                    }
 
-                    Ok(expr)
+                    funs.push(fun)
                 },
 
-                _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected symbol: {}", self.traveler.current_content()))),
-            },
-            _ => Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected: {:#?}", self.traveler.current_content()))),
-        }
-    }
-    
-    fn block(&mut self) -> ParserResult<Vec<Statement>> {        
-        match self.traveler.current().token_type.clone() {
-            TokenType::Block(ref v) => {
-                let mut p = Parser::new(Traveler::new(v.clone()));
-                Ok(try!(p.parse()))
-            },
-            _ => Ok(vec![Statement::Expression(Rc::new(self.expression()?))]),
-        }
-    }
+                None => {
+                    let name = Rc::new(self.traveler.expect(TokenType::Identifier)?);
+                    self.traveler.next();
 
-    fn call(&mut self, caller: Expression) -> ParserResult<Expression> {
-        let mut args = Vec::new();
+                    if names.contains(&name) || fields.iter().any(|(n, _)| *n == name) {
+                        return Err(ParserError::new_pos(position, &format!("{}: declared twice in '{}'", name, owner)))
+                    }
 
-        while self.traveler.current_content() != ")" && self.traveler.current_content() != "\n" {
-            args.push(try!(self.expression()));
+                    names.push(name.clone());
 
-            self.traveler.next();
+                    match self.types()? {
+                        Some(t) => fields.push((name, t)),
+                        None    => return Err(ParserError::new_pos(position, &format!("{}: expected field type", name))),
+                    }
 
-            if self.traveler.current_content() == "," {
-                self.traveler.next();
+                    if self.traveler.current_content() == "," {
+                        self.traveler.next();
+                    }
+                },
             }
+
+            // members name the owner without its members, complete() finds them
+            self.types.borrow_mut().insert(owner.to_string(), declared(fields, &[]));
         }
-        
-        if self.traveler.current_content() == ")" {
-            self.traveler.next();
-        }
-        
-        Ok(Expression::Call(Rc::new(caller), Rc::new(args)))
+
+        self.types.borrow_mut().insert(owner.to_string(), declared(fields, methods));
+
+        Ok(funs)
     }
-    
-    fn types(&mut self) -> ParserResult<Option<Type>> {
-        match self.traveler.current().token_type {
-            TokenType::Type   => {
-                let t = get_type(&self.traveler.current_content()).unwrap();
-                self.traveler.next();
 
-                match self.traveler.current_content().as_str() {
-                    ".." => {
-                        self.traveler.next();
-                        
-                        Ok(Some(Type::Array(Rc::new(t))))
-                    },
+    fn expression(&mut self) -> ParserResult<Expression> {
+        let expr = self.value()?;
 
-                    _ => Ok(Some(t))
-                }
-            },
-            _ => Ok(None),
+        if expr == Expression::Eof {
+            return Ok(expr)
         }
+
+        self.pipe(expr)
     }
 
-    fn expression(&mut self) -> ParserResult<Expression> {
+    // an expression without pipes, as in call args
+    fn value(&mut self) -> ParserResult<Expression> {
         if self.traveler.current_content() == "\n" {
             self.traveler.next();
         }
         
         let expr = self.term()?;
 
-        if expr == Expression::EOF {
+        if expr == Expression::Eof {
             return Ok(expr)
         }
 
@@ -519,7 +2207,11 @@ impl Parser {
         
         if self.traveler.remaining() > 0 {
             if self.traveler.current().token_type == TokenType::Operator {
-                return self.operation(expr)
+                let operation = self.operation(expr)?;
+
+                self.traveler.prev();
+
+                return Ok(operation)
             }
             
             self.traveler.prev();
@@ -527,6 +2219,69 @@ impl Parser {
 
         Ok(expr)
     }
+
+    // x |> f(a) binds looser than any operator
+    fn pipe(&mut self, expression: Expression) -> ParserResult<Expression> {
+        let mut expr = expression;
+
+        loop {
+            self.traveler.next();
+
+            if self.traveler.current_content() != "|>" {
+                self.traveler.prev();
+
+                return Ok(expr)
+            }
+
+            self.traveler.next();
+
+            let mut callee = Expression::Identifier(Rc::new(self.traveler.expect(TokenType::Identifier)?));
+            self.traveler.next();
+
+            while self.traveler.current_content() == "." {
+                self.traveler.next();
+
+                let field = Expression::Identifier(Rc::new(self.traveler.expect(TokenType::Identifier)?));
+                callee = Expression::Index(Rc::new(callee), Rc::new(field), Rc::new(Cell::new(false)));
+
+                self.traveler.next();
+            }
+
+            let call = match self.traveler.current().token_type {
+                TokenType::Block(_) | TokenType::Eol | TokenType::Eof => {
+                    self.traveler.prev();
+
+                    Expression::Call(Rc::new(callee), Rc::new(Vec::new()))
+                },
+
+                TokenType::Operator => return Err(ParserError::new_pos(self.traveler.current().position, &format!("unexpected '{}' after pipe target", self.traveler.current_content()))),
+
+                _ => match self.traveler.current_content().as_str() {
+                    "(" if self.is_attached() => {
+                        self.traveler.next();
+
+                        let call = self.call(callee)?;
+
+                        self.traveler.next();
+                        self.traveler.expect_content(")")?;
+
+                        call
+                    },
+
+                    "\n" | ")" | "," | "]" | "|>" | ":" => {
+                        self.traveler.prev();
+
+                        Expression::Call(Rc::new(callee), Rc::new(Vec::new()))
+                    },
+
+                    // x |> f a, b
+                    _ => self.call(callee)?,
+                },
+            };
+
+            expr = Expression::Pipe(Rc::new(expr), Rc::new(call))
+        }
+    }
     
     fn operation(&mut self, expression: Expression) -> ParserResult<Expression> {
         let mut ex_stack = vec![expression];
@@ -589,3 +2344,28 @@ impl Parser {
         Ok(ex_stack.pop().unwrap())
     }
 }
+
+// a.b.c parses as a.(b.c), this turns it into (a.b).c
+fn index(base: Expression, key: &Rc<Expression>) -> Expression {
+    match **key {
+        Expression::Index(ref a, ref b, ref custom) => Expression::Index(Rc::new(index(base, a)), b.clone(), custom.clone()),
+        Expression::Operation { ref left, ref op, ref right, } => Expression::Operation {
+            left:  Rc::new(index(base, left)),
+            op:    op.clone(),
+            right: right.clone(),
+        },
+        Expression::Pipe(ref left, ref right) => Expression::Pipe(Rc::new(index(base, left)), right.clone()),
+        _                               => Expression::Index(Rc::new(base), key.clone(), Rc::new(Cell::new(false))),
+    }
+}
+
+// the metamethod an op implements
+fn metamethod(symbol: &str) -> Option<&'static str> {
+    match symbol {
+        "[" | "[]" => Some("__index"),
+        ".."       => Some("__concat"),
+        // comparisons lua derives from these
+        "!=" | ">" | ">=" => None,
+        symbol     => get_operand(symbol).and_then(|(op, _)| op.metamethod()),
+    }
+}
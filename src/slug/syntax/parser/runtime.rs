@@ -0,0 +1,55 @@
+use std::cell::Cell;
+
+// set when lua using the async runtime is written
+thread_local!(static ASYNC_USED: Cell<bool> = const { Cell::new(false) });
+
+pub fn use_async() {
+    ASYNC_USED.with(|u| u.set(true))
+}
+
+// whether what was written since the last call needs the runtime
+pub fn async_used() -> bool {
+    ASYNC_USED.with(|u| u.replace(false))
+}
+
+// tasks are coroutines, resumed by whatever they await once it's done
+pub const ASYNC_RUNTIME: &str = r##"local M = {}
+local current = nil
+local pack = function(...) return {n = select("#", ...), ...} end
+local unpack = table.unpack or unpack
+local function finish(task, results)
+task.done = true
+task.results = results
+for _, waiter in ipairs(task.waiters) do
+M.resume(waiter)
+end
+end
+function M.resume(task)
+local outer = current
+current = task
+local results = pack(coroutine.resume(task.co))
+current = outer
+if not results[1] then error(results[2], 0) end
+if coroutine.status(task.co) == "dead" then finish(task, results) end
+end
+function M.run(fn)
+local task = {co = coroutine.create(fn), waiters = {}}
+M.resume(task)
+return task
+end
+-- for the host to complete, as timers and callbacks do
+function M.pending()
+return {waiters = {}}
+end
+function M.complete(task, ...)
+finish(task, pack(true, ...))
+end
+function M.await(task)
+if not task.done then
+table.insert(task.waiters, current)
+coroutine.yield()
+end
+return unpack(task.results, 2, task.results.n)
+end
+return M
+"##;
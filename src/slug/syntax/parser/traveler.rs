@@ -32,6 +32,14 @@ impl Traveler {
         false
     }
 
+    pub fn checkpoint(&self) -> usize {
+        self.top
+    }
+
+    pub fn restore(&mut self, checkpoint: usize) {
+        self.top = checkpoint
+    }
+
     pub fn remaining(&self) -> usize {
         self.tokens.len() - self.top + 1
     }
@@ -61,7 +69,7 @@ impl Traveler {
     }
 
     pub fn expect_content(&self, content: &str) -> ParserResult<String> {
-        if &self.current_content() == content {
+        if self.current_content() == content {
             Ok(self.current_content())
         } else {
             Err(ParserError::new(&format!("expected '{}', found '{}'", content, self.current_content())))
@@ -69,11 +77,9 @@ impl Traveler {
     }
 
     pub fn expect_contents(&self, sequence: Vec<String>) -> Result<Vec<&Token>, String> {
-        let mut accum: usize = 0;
-
         let mut res = Vec::new();
 
-        for c in sequence {
+        for (accum, c) in sequence.into_iter().enumerate() {
             if self.top + accum >= self.tokens.len() {
                 return Err(format!("expected '{}', found end of source >:(", c))
             }
@@ -82,9 +88,7 @@ impl Traveler {
                 return Err(format!("expected '{}', found '{}'", c, self.tokens[self.top + accum].content()))
             }
 
-            res.push(self.get(self.top + accum));
-
-            accum += 1
+            res.push(self.get(self.top + accum))
         }
 
         Ok(res)
@@ -4,9 +4,17 @@ use std::collections::HashMap;
 
 use std::fmt;
 
+use super::Loader;
+use super::parser::Type;
+
 pub struct SymTab {
     parent: Option<Rc<SymTab>>,
     names:  RefCell<HashMap<String, usize>>,
+    params: RefCell<HashMap<String, Rc<Vec<Rc<String>>>>>, // param names of declared funs
+    definitions: RefCell<HashMap<String, Type>>, // declared records and classes, with every member
+    warnings: RefCell<Vec<String>>, // only collected in the global table
+    loader: Option<Rc<Loader>>, // only in the global table, when there are files to import
+    narrowing: bool, // only holds narrower types of names declared further out
 }
 
 impl SymTab {
@@ -20,6 +28,11 @@ impl SymTab {
         SymTab {
             parent: Some(parent),
             names:  RefCell::new(hash_names),
+            params: RefCell::new(HashMap::new()),
+            definitions: RefCell::new(HashMap::new()),
+            warnings: RefCell::new(Vec::new()),
+            loader: None,
+            narrowing: false,
         }
     }
 
@@ -27,6 +40,25 @@ impl SymTab {
         SymTab {
             parent: None,
             names:  RefCell::new(HashMap::new()),
+            params: RefCell::new(HashMap::new()),
+            definitions: RefCell::new(HashMap::new()),
+            warnings: RefCell::new(Vec::new()),
+            loader: None,
+            narrowing: false,
+        }
+    }
+
+    pub fn new_narrowing(parent: Rc<SymTab>) -> SymTab {
+        SymTab {
+            narrowing: true,
+            ..SymTab::new(parent, &[])
+        }
+    }
+
+    pub fn new_module(loader: Rc<Loader>) -> SymTab {
+        SymTab {
+            loader: Some(loader),
+            ..SymTab::new_global()
         }
     }
 
@@ -56,29 +88,92 @@ impl SymTab {
         }
     }
 
-    pub fn visualize(&self, env_index: usize) {
-        if env_index > 0 {
-            if let Some(ref p) = self.parent {
-                p.visualize(env_index - 1);
-                println!("------------------------------");
+    // where a name was declared, past any narrowings of it
+    pub fn get_declared_name(&self, name: &str) -> Option<(usize, usize)> {
+        self.get_declared_internal(name, 0)
+    }
+
+    fn get_declared_internal(&self, name: &str, env_index: usize) -> Option<(usize, usize)> {
+        if !self.narrowing {
+            if let Some(index) = self.names.borrow().get(name) {
+                return Some((*index, env_index));
             }
         }
 
-        for (i, v) in self.names.borrow().iter().enumerate() {
-            println!("({} : {}) = {:?}", i, env_index, v)
+        match self.parent {
+            Some(ref parent) => parent.get_declared_internal(name, env_index + 1),
+            None => None,
+        }
+    }
+
+    pub fn add_params(&self, name: &str, params: Rc<Vec<Rc<String>>>) {
+        self.params.borrow_mut().insert(name.to_string(), params);
+    }
+
+    pub fn get_params(&self, name: &str) -> Option<Rc<Vec<Rc<String>>>> {
+        if self.names.borrow().contains_key(name) {
+            return self.params.borrow().get(name).cloned()
+        }
+
+        match self.parent {
+            Some(ref parent) => parent.get_params(name),
+            None => None,
+        }
+    }
+
+    pub fn add_definition(&self, name: &str, t: Type) {
+        self.definitions.borrow_mut().insert(name.to_string(), t);
+    }
+
+    pub fn get_definition(&self, name: &str) -> Option<Type> {
+        if self.names.borrow().contains_key(name) {
+            return self.definitions.borrow().get(name).cloned()
+        }
+
+        match self.parent {
+            Some(ref parent) => parent.get_definition(name),
+            None => None,
+        }
+    }
+
+    pub fn warn(&self, warning: &str) {
+        match self.parent {
+            Some(ref parent) => parent.warn(warning),
+            None => {
+                let mut warnings = self.warnings.borrow_mut();
+
+                // scopes may be visited more than once
+                if !warnings.iter().any(|w| w == warning) {
+                    warnings.push(warning.to_string())
+                }
+            },
+        }
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        match self.parent {
+            Some(ref parent) => parent.warnings(),
+            None => self.warnings.borrow().clone(),
+        }
+    }
+
+    pub fn loader(&self) -> Option<Rc<Loader>> {
+        match self.parent {
+            Some(ref parent) => parent.loader(),
+            None => self.loader.clone(),
         }
     }
 
     fn dump(&self, f: &mut fmt::Formatter, env_index: usize) -> fmt::Result {
         if env_index > 0 {
             if let Some(ref p) = self.parent {
-                try!(p.dump(f, env_index - 1));
-                try!(writeln!(f, "------------------------------"));
+                p.dump(f, env_index - 1)?;
+                writeln!(f, "------------------------------")?;
             }
         }
 
         for (i, v) in self.names.borrow().iter().enumerate() {
-            try!(writeln!(f, "({} : {}) = {:?}", i, env_index, v))
+            writeln!(f, "({} : {}) = {:?}", i, env_index, v)?
         }
 
         Ok(())
@@ -87,7 +182,7 @@ impl SymTab {
 
 impl fmt::Debug for SymTab {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        try!(self.dump(f, 0));
+        self.dump(f, 0)?;
         Ok(())
     }
 }
@@ -1,5 +1,5 @@
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 
 use super::{RunResult, RunError};
@@ -9,13 +9,21 @@ use super::parser::Type;
 pub struct TypeTab {
     parent: Option<Rc<TypeTab>>,
     types: RefCell<Vec<Type>>,
+    returns: RefCell<Option<Type>>, // set in fun bodies
+    awaits: Cell<bool>, // set in async fun bodies
+    yields: RefCell<Option<Type>>, // set in generator bodies
+    fixed: RefCell<Vec<Rc<String>>>, // type params of generic fun bodies
 }
 
 impl TypeTab {
-    pub fn new(parent: Rc<TypeTab>, types: &Vec<Type>) -> TypeTab {
+    pub fn new(parent: Rc<TypeTab>, types: &[Type]) -> TypeTab {
         TypeTab {
             parent: Some(parent),
-            types: RefCell::new(types.clone()),
+            types: RefCell::new(types.to_vec()),
+            returns: RefCell::new(None),
+            awaits: Cell::new(false),
+            yields: RefCell::new(None),
+            fixed: RefCell::new(Vec::new()),
         }
     }
 
@@ -23,18 +31,10 @@ impl TypeTab {
         TypeTab {
             parent: None,
             types: RefCell::new(Vec::new()),
-        }
-    }
-
-    pub fn new_partial(parent: Rc<TypeTab>, types: &[Type], size: usize) -> TypeTab {
-        let mut stack = types.to_vec();
-        for _ in 0 .. size - types.len() {
-            stack.push(Type::Undefined)
-        }
-
-        TypeTab {
-            parent: Some(parent),
-            types: RefCell::new(stack),
+            returns: RefCell::new(None),
+            awaits: Cell::new(false),
+            yields: RefCell::new(None),
+            fixed: RefCell::new(Vec::new()),
         }
     }
 
@@ -70,29 +70,90 @@ impl TypeTab {
         }
     }
 
-    pub fn visualize(&self, env_index: usize) {
-        if env_index > 0 {
-            if let Some(ref p) = self.parent {
-                p.visualize(env_index - 1);
-                println!("------------------------------");
-            }
+    pub fn set_returns(&self, t: Type) {
+        *self.returns.borrow_mut() = Some(t)
+    }
+
+    // what the innermost fun returns, if inside one
+    pub fn returns(&self) -> Option<Type> {
+        if let Some(ref t) = *self.returns.borrow() {
+            return Some(t.clone())
         }
 
-        for (i, v) in self.types.borrow().iter().enumerate() {
-            println!("({} : {}) = {:?}", i, env_index, v)
+        match self.parent {
+            Some(ref p) => p.returns(),
+            None => None,
+        }
+    }
+
+    pub fn set_async(&self, awaits: bool) {
+        self.awaits.set(awaits)
+    }
+
+    // whether the innermost fun is async
+    pub fn in_async(&self) -> bool {
+        if self.returns.borrow().is_some() {
+            return self.awaits.get()
+        }
+
+        match self.parent {
+            Some(ref p) => p.in_async(),
+            None => false,
+        }
+    }
+
+    pub fn set_yields(&self, t: Option<Type>) {
+        *self.yields.borrow_mut() = t
+    }
+
+    // what the innermost fun yields, if it's a generator
+    pub fn yields(&self) -> Option<Type> {
+        if self.returns.borrow().is_some() {
+            return self.yields.borrow().clone()
+        }
+
+        match self.parent {
+            Some(ref p) => p.yields(),
+            None => None,
+        }
+    }
+
+    pub fn set_fixed(&self, vars: Vec<Rc<String>>) {
+        *self.fixed.borrow_mut() = vars
+    }
+
+    // the type params of every fun around, which stay as they are within them
+    pub fn fixed(&self) -> Vec<Rc<String>> {
+        let mut vars = self.fixed.borrow().clone();
+
+        if let Some(ref p) = self.parent {
+            vars.extend(p.fixed())
+        }
+
+        vars
+    }
+
+    // the type a generator was found to yield
+    pub fn yielded(&self, t: Type) {
+        if self.returns.borrow().is_some() {
+            return self.set_yields(Some(t))
+        }
+
+        if let Some(ref p) = self.parent {
+            p.yielded(t)
         }
     }
 
     fn dump(&self, f: &mut fmt::Formatter, env_index: usize) -> fmt::Result {
         if env_index > 0 {
             if let Some(ref p) = self.parent {
-                try!(p.dump(f, env_index - 1));
-                try!(writeln!(f, "------------------------------"));
+                p.dump(f, env_index - 1)?;
+                writeln!(f, "------------------------------")?;
             }
         }
 
         for (i, v) in self.types.borrow().iter().enumerate() {
-            try!(writeln!(f, "({} : {}) = {:?}", i, env_index, v))
+            writeln!(f, "({} : {}) = {:?}", i, env_index, v)?
         }
 
         Ok(())
@@ -109,7 +170,7 @@ impl TypeTab {
 
 impl fmt::Debug for TypeTab {
     fn fmt(&self, f : &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        try!(self.dump(f, 0));
+        self.dump(f, 0)?;
         Ok(())
     }
 }
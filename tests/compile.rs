@@ -0,0 +1,894 @@
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// a program compiled by the slug binary, in a directory of its own
+struct Compiled {
+    output:  String,
+    dir:     PathBuf,
+    success: bool, // how the compiler exited
+}
+
+impl Compiled {
+    fn lua(&self, module: &str) -> String {
+        fs::read_to_string(self.dir.join(module).with_extension("lua")).unwrap_or_default()
+    }
+
+    fn ok(&self) -> String {
+        assert!(!self.output.contains("error"), "{}", self.output);
+        assert!(self.success, "{}", self.output);
+        self.lua("main")
+    }
+}
+
+impl Drop for Compiled {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+// main.slug and the modules it imports
+fn compile_files(files: &[(&str, &str)]) -> Compiled {
+    let dir = env::temp_dir().join(format!("slug-{}-{}", std::process::id(), COUNT.fetch_add(1, Ordering::SeqCst)));
+    fs::create_dir_all(&dir).unwrap();
+
+    for &(name, source) in files {
+        fs::write(dir.join(name).with_extension("slug"), source).unwrap();
+    }
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_slug"))
+        .arg(dir.join("main.slug"))
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let start = Instant::now();
+
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status
+        }
+
+        if start.elapsed() > Duration::from_secs(10) {
+            child.kill().unwrap();
+            panic!("the compiler hangs")
+        }
+
+        thread::sleep(Duration::from_millis(10))
+    };
+
+    let mut output = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+
+    Compiled { output, dir, success: status.success() }
+}
+
+fn compile(source: &str) -> Compiled {
+    compile_files(&[("main", source)])
+}
+
+#[test]
+fn definitions_are_checked_and_become_locals() {
+    let lua = compile("x = 1\ny = x + 2\n").ok();
+
+    assert!(lua.contains("local x = 1\nlocal y = x + 2"), "{}", lua);
+
+    let compiled = compile("x num = 1\ny str = x\n");
+
+    assert!(compiled.output.contains("y: expected 'Str', got 'Num'"), "{}", compiled.output);
+
+    let compiled = compile("z = q + 1\n");
+
+    assert!(compiled.output.contains("unexpected use of: q"), "{}", compiled.output);
+}
+
+#[test]
+fn variadic_params_check_every_arg() {
+    let source = "fun log (fmt str, args num..) num:\n  args.(1)\n";
+
+    let lua = compile(&format!("{}log \"a\", 1, 2\nlog \"b\"\n", source)).ok();
+
+    assert!(lua.contains("function log(fmt,...)"), "{}", lua);
+    assert!(lua.contains("local args = {n = select(\"#\", ...), ...}"), "{}", lua);
+    assert!(lua.contains("log(\"a\",1,2)"), "{}", lua);
+    assert!(lua.contains("log(\"b\")"), "{}", lua);
+
+    // the fixed params are checked, then every repeated one
+    let compiled = compile(&format!("{}log 1, 2\n", source));
+
+    assert!(compiled.output.contains("log: expected 'Str' got 'Num'"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}log \"a\", 1, \"x\"\n", source));
+
+    assert!(compiled.output.contains("log: expected 'Num' got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}log!\n", source));
+
+    assert!(compiled.output.contains("log: expected at least 1 args, got 0"), "{}", compiled.output);
+
+    // inside, the args are an array of the repeated type
+    let compiled = compile("fun f (xs num..):\n  s str = xs\n");
+
+    assert!(compiled.output.contains("s: expected 'Str', got 'Array(Num)'"), "{}", compiled.output);
+}
+
+#[test]
+fn defaulted_params_may_be_left_out() {
+    let source = "fun connect (host str, port num = 8080, tries num = 3) num:\n  port + tries\n";
+
+    let lua = compile(&format!("{}a = connect \"h\"\nb = connect \"h\", 1\nc = connect \"h\", 1, 2\n", source)).ok();
+
+    assert!(lua.contains("function connect(host,port,tries)\nif port == nil then port = 8080 end\nif tries == nil then tries = 3 end\n"), "{}", lua);
+    assert!(lua.contains("local a = connect(\"h\")"), "{}", lua);
+    assert!(lua.contains("local b = connect(\"h\",1)"), "{}", lua);
+    assert!(lua.contains("local c = connect(\"h\",1,2)"), "{}", lua);
+
+    let compiled = compile(&format!("{}a = connect!\n", source));
+
+    assert!(compiled.output.contains("connect: expected at least 1 args, got 0"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}a = connect \"h\", 1, 2, 3\n", source));
+
+    assert!(compiled.output.contains("connect: expected at most 3 args, got 4"), "{}", compiled.output);
+
+    // a given arg is still checked against its param
+    let compiled = compile(&format!("{}a = connect \"h\", \"x\"\n", source));
+
+    assert!(compiled.output.contains("connect: expected 'Num' got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile("fun connect (host str, port num = \"x\") num:\n  port\n");
+
+    assert!(compiled.output.contains("port: expected default 'Num', got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile("fun connect (host str = \"h\", port num) num:\n  port\n");
+
+    assert!(compiled.output.contains("expected default for: port"), "{}", compiled.output);
+
+    let lua = compile("fun log (level num = 1, args any..) num:\n  level\nlog!\nlog 2, \"a\", 3\n").ok();
+
+    assert!(lua.contains("function log(level,...)"), "{}", lua);
+    assert!(lua.contains("\nlog()\nlog(2,\"a\",3)\n"), "{}", lua);
+
+    let compiled = compile("fun log (level num = 1, args any.., tag str) num:\n  level\n");
+
+    assert!(compiled.output.contains("expected default for: args"), "{}", compiled.output);
+}
+
+#[test]
+fn named_args_are_put_in_param_order() {
+    let source = "fun spawn (x num, y num, kind str) num:\n  x\n";
+
+    let lua = compile(&format!("{}a = spawn y: 2, kind: \"orc\", x: 1\nb = spawn 1, kind: \"orc\", y: 2\n", source)).ok();
+
+    assert!(lua.contains("local a = spawn(1,2,\"orc\")"), "{}", lua);
+    assert!(lua.contains("local b = spawn(1,2,\"orc\")"), "{}", lua);
+
+    let compiled = compile(&format!("{}a = spawn 1, x: 2\n", source));
+
+    assert!(compiled.output.contains("spawn: arg 'x' supplied twice"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}a = spawn z: 2\n", source));
+
+    assert!(compiled.output.contains("spawn: unknown arg 'z'"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}a = spawn x: \"a\", y: 2, kind: \"orc\"\n", source));
+
+    assert!(compiled.output.contains("spawn: expected 'Num' got 'Str'"), "{}", compiled.output);
+
+    // named args fill the fixed params of a variadic fun, leaving the rest empty
+    let source = "fun log (level num, tag str, args num..) num:\n  level\n";
+
+    let lua = compile(&format!("{}a = log tag: \"t\", level: 2\nb = log 1, \"t\", 5, 6\n", source)).ok();
+
+    assert!(lua.contains("local a = log(2,\"t\")"), "{}", lua);
+    assert!(lua.contains("local b = log(1,\"t\",5,6)"), "{}", lua);
+
+    let compiled = compile(&format!("{}a = log tag: 1, level: 2\n", source));
+
+    assert!(compiled.output.contains("log: expected 'Str' got 'Num'"), "{}", compiled.output);
+}
+
+#[test]
+fn method_calls_become_colon_calls() {
+    let lua = compile("o any = 1\np = o::pos!\no::move 1, 2\n").ok();
+
+    assert!(lua.contains("local p = o:pos()"), "{}", lua);
+    assert!(lua.contains("\no:move(1, 2)\n"), "{}", lua);
+
+    let lua = compile("o any = 1\nq = o.inner::f 1\n").ok();
+
+    assert!(lua.contains("local q = o.inner:f(1)"), "{}", lua);
+
+    let lua = compile("o any = 1\nr = o::f o::g 1\n").ok();
+
+    assert!(lua.contains("local r = o:f(o:g(1))"), "{}", lua);
+}
+
+#[test]
+fn generics_are_instantiated_per_call() {
+    let source = "fun first<T> (xs T..) T:\n  xs.(1)\nfun pick<T> (a T, b T) T: a\n";
+
+    // each call binds T on its own
+    let lua = compile(&format!("{}n num = first 1, 2, 3\ns str = first \"a\"\nm num = pick 1, 2\n", source)).ok();
+
+    assert!(lua.contains("local n = first(1,2,3)"), "{}", lua);
+    assert!(lua.contains("local s = first(\"a\")"), "{}", lua);
+
+    // the first arg binds T, the rest must agree
+    let compiled = compile(&format!("{}x = pick 1, \"a\"\n", source));
+
+    assert!(compiled.output.contains("pick: expected 'Num' got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}s str = first 1, 2\n", source));
+
+    assert!(compiled.output.contains("s: expected 'Str', got 'Num'"), "{}", compiled.output);
+}
+
+#[test]
+fn generic_records_take_type_args() {
+    let source = "record Pair<A, B>:\n  a A\n  b B\n";
+
+    let lua = compile(&format!("{}p Pair<num, str> = Pair 1, \"x\"\ns str = p.b\n", source)).ok();
+
+    assert!(lua.contains("local p = Pair(1,\"x\")"), "{}", lua);
+
+    let compiled = compile(&format!("{}p Pair<num, str> = Pair 1, \"x\"\nn num = p.b\n", source));
+
+    assert!(compiled.output.contains("n: expected 'Num', got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}p Pair<num, str> = Pair \"x\", 1\n", source));
+
+    assert!(compiled.output.contains("p: expected 'Record(\"Pair\""), "{}", compiled.output);
+}
+
+#[test]
+fn generic_funs_pass_as_args() {
+    let source = "fun id<U> (x U) U: x\nfun ap<T> (f fun(T) T, x T) T: f x\n";
+
+    let lua = compile(&format!("{}a num = ap id, 1\nb str = ap id, \"b\"\nfun twice<T> (g fun(T) T, x T) T: ap g, ap g, x\n", source)).ok();
+
+    assert!(lua.contains("local a = ap(id,1)"), "{}", lua);
+    assert!(lua.contains("return ap(g,ap(g,x))"), "{}", lua);
+
+    let compiled = compile(&format!("{}a str = ap id, 1\n", source));
+
+    assert!(compiled.output.contains("a: expected 'Str', got 'Num'"), "{}", compiled.output);
+
+    // within twice, T is whatever its caller picks, not num
+    let compiled = compile(&format!("{}fun twice<T> (g fun(T) T) num: ap g, 1\n", source));
+
+    assert!(compiled.output.contains("ap: expected 'Var(\"T\")' got 'Num'"), "{}", compiled.output);
+
+    let compiled = compile("fun none<T> () T?: nil\nn = none!\n");
+
+    assert!(compiled.output.contains("none: can't infer type param 'T'"), "{}", compiled.output);
+}
+
+#[test]
+fn unions_take_their_members() {
+    // members are sorted and deduplicated
+    let compiled = compile("x str | num | str = 1\ny bool = x\n");
+
+    assert!(compiled.output.contains("y: expected 'Bool', got 'Union([Num, Str])'"), "{}", compiled.output);
+
+    let compiled = compile("x num | str = true\n");
+
+    assert!(compiled.output.contains("x: expected 'Union([Num, Str])', got 'Bool'"), "{}", compiled.output);
+
+    // a member fits the union, not the other way round
+    compile("x num = 1\ny num | str = x\n").ok();
+
+    let compiled = compile("x num | str = 1\ny num = x\n");
+
+    assert!(compiled.output.contains("y: expected 'Num', got 'Union([Num, Str])'"), "{}", compiled.output);
+}
+
+#[test]
+fn unions_narrow_on_literals() {
+    let source = "fun f (v num | bool) num:\n  if v == 1:\n    v * 2\n  else: 0\n";
+
+    let lua = compile(&format!("{}x num | bool = true\ny = f x\nz = f 1\n", source)).ok();
+
+    assert!(lua.contains("if v == 1 then\nreturn v * 2"), "{}", lua);
+    assert!(lua.contains("local y = f(x)"), "{}", lua);
+
+    let compiled = compile(&format!("{}z = f \"a\"\n", source));
+
+    assert!(compiled.output.contains("f: expected 'Union([Bool, Num])' got 'Str'"), "{}", compiled.output);
+
+    // without narrowing, every member must support the operation
+    let compiled = compile("fun f (v num | bool):\n  w = v * 2\n");
+
+    assert!(compiled.output.contains("failed to multiply: Bool and Num"), "{}", compiled.output);
+
+    compile("fun f (v num | str) str:\n  v ++ \"!\"\n").ok();
+}
+
+#[test]
+fn tuples_destructure_and_truncate() {
+    let source = "fun divmod (a num, b num) (num, num):\n  return a / b, a % b\n";
+
+    let lua = compile(&format!("{}q, r = divmod 7, 2\nfirst num = divmod 7, 2\nx = (divmod 7, 2) + 1\n", source)).ok();
+
+    assert!(lua.contains("return a / b, a % b"), "{}", lua);
+    assert!(lua.contains("local q, r = divmod(7,2)"), "{}", lua);
+    assert!(lua.contains("local first = divmod(7,2)"), "{}", lua);
+    assert!(lua.contains("local x = divmod(7,2) + 1"), "{}", lua);
+
+    let compiled = compile(&format!("{}q, r, s = divmod 7, 2\n", source));
+
+    assert!(compiled.output.contains("expected 3 values, got 2"), "{}", compiled.output);
+
+    // one value is the first
+    let compiled = compile(&format!("{}q str = divmod 7, 2\n", source));
+
+    assert!(compiled.output.contains("q: expected 'Str', got 'Num'"), "{}", compiled.output);
+}
+
+#[test]
+fn returns_match_the_tuple() {
+    let compiled = compile("fun f (a num) (num, str):\n  return a, a\n");
+
+    assert!(compiled.output.contains("return: expected 'Tuple([Num, Str])', got 'Tuple([Num, Num])'"), "{}", compiled.output);
+
+    let compiled = compile("fun f (a num) (num, str):\n  return a\n");
+
+    assert!(compiled.output.contains("return: expected 'Tuple([Num, Str])', got 'Num'"), "{}", compiled.output);
+}
+
+#[test]
+fn function_params_are_contravariant() {
+    let lua = compile("fun apply (f fun(num) num, x num) num:\n  f x\nfun wide (n num | bool) num: 1\na = apply wide, 1\n").ok();
+
+    assert!(lua.contains("local a = apply(wide,1)"), "{}", lua);
+
+    let compiled = compile("fun apply (f fun(num | bool) num, x num) num:\n  f x\nfun narrow (n num) num: 1\na = apply narrow, 1\n");
+
+    assert!(compiled.output.contains("apply: expected 'Fun([Num, Union([Bool, Num])], 1)' got 'Fun([Num, Num], 1)'"), "{}", compiled.output);
+
+    let compiled = compile("fun apply (f fun(num) num, x num) num:\n  f x\nfun two (a num, b num) num: a\nc = apply two, 1\n");
+
+    assert!(compiled.output.contains("apply: expected 'Fun([Num, Num], 1)' got 'Fun([Num, Num, Num], 2)'"), "{}", compiled.output);
+}
+
+#[test]
+fn function_returns_are_covariant() {
+    let lua = compile("cb fun(num) num | str = fun (n num) num: 1\n").ok();
+
+    assert!(lua.contains("local cb = function(n)"), "{}", lua);
+
+    let compiled = compile("cb fun(num) num = fun (n num) num | str: 1\n");
+
+    assert!(compiled.output.contains("cb: expected 'Fun([Num, Num], 1)', got 'Fun([Union([Num, Str]), Num], 1)'"), "{}", compiled.output);
+
+    // calls through an annotated name are checked like any other
+    let compiled = compile("cb fun(num) str = fun (n num) str: \"a\"\ns str = cb \"x\"\n");
+
+    assert!(compiled.output.contains("cb: expected 'Num' got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile("cb fun(num) str = fun (n num) str: \"a\"\nn num = cb 1\n");
+
+    assert!(compiled.output.contains("n: expected 'Num', got 'Str'"), "{}", compiled.output);
+}
+
+#[test]
+fn class_methods_take_self_implicitly() {
+    let source = "class C:\n  x num\n  fun move (dx num) num:\n    self.x + dx\nc = C.new 1\n";
+
+    let lua = compile(&format!("{}c::move 2\nd = c::move 2\n", source)).ok();
+
+    assert!(lua.contains("function C.move(self,dx)"), "{}", lua);
+    assert!(lua.contains("\nc:move(2)\n"), "{}", lua);
+    assert!(lua.contains("local d = c:move(2)"), "{}", lua);
+
+    let compiled = compile(&format!("{}d = c::move \"a\"\n", source));
+
+    assert!(compiled.output.contains("move: expected 'Num' got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}d = c::move 1, 2\n", source));
+
+    assert!(compiled.output.contains("move: expected at most 1 args, got 2"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}s str = c::move 2\n", source));
+
+    assert!(compiled.output.contains("s: expected 'Str', got 'Num'"), "{}", compiled.output);
+
+    let compiled = compile(&format!("{}d = c::jump 2\n", source));
+
+    assert!(compiled.output.contains("c: no field 'jump' in 'C'"), "{}", compiled.output);
+
+    let source = "class C:\n  x num\n  fun move (dx num, dy num) num:\n    self.x + dx + dy\nc = C.new 1\n";
+
+    let lua = compile(&format!("{}d = c::move(1, 2)\ne = c::move(1, 2) * 2\nc::move(1, 2)\n", source)).ok();
+
+    assert!(lua.contains("local d = c:move(1, 2)"), "{}", lua);
+    assert!(lua.contains("local e = c:move(1, 2) * 2"), "{}", lua);
+    assert!(lua.contains("\nc:move(1, 2)\n"), "{}", lua);
+
+    let compiled = compile(&format!("{}d = c::move(1, \"a\")\n", source));
+
+    assert!(compiled.output.contains("move: expected 'Num' got 'Str'"), "{}", compiled.output);
+}
+
+#[test]
+fn await_only_in_async_funs() {
+    let compiled = compile("async fun wait (n num) num: n\nasync fun go num:\n  x = await wait 1\n  x + 1\n");
+    let lua = compiled.ok();
+
+    assert!(lua.starts_with("local __async = require(\"__async\")"), "{}", lua);
+    assert!(lua.contains("local x = __async.await(wait(1))"), "{}", lua);
+    assert!(compiled.lua("__async").contains("coroutine"), "{}", compiled.lua("__async"));
+
+    let compiled = compile("fun wait (n num) num: n\nfun go num:\n  await wait 1\n");
+
+    assert!(compiled.output.contains("only async funs can wait"), "{}", compiled.output);
+
+    // a plain fun inside an async one can't wait either
+    let compiled = compile("async fun go:\n  f = fun num:\n    await go!\n");
+
+    assert!(compiled.output.contains("await go(): only async funs can wait"), "{}", compiled.output);
+
+    let compiled = compile("async fun go num:\n  x = await 1\n  x\n");
+
+    assert!(compiled.output.contains("await 1: expected async, got 'Num'"), "{}", compiled.output);
+}
+
+#[test]
+fn async_results_are_awaited_for_their_value() {
+    let compiled = compile("async fun wait (n num) num: n\nw = wait 1\nn num = w\n");
+
+    assert!(compiled.output.contains("n: expected 'Num', got 'Async(Num)'"), "{}", compiled.output);
+
+    let compiled = compile("async fun wait (n num) num: n\nasync fun go num:\n  x str = await wait 1\n  1\n");
+
+    assert!(compiled.output.contains("x: expected 'Str', got 'Num'"), "{}", compiled.output);
+
+    // one runtime for every module that waits
+    let compiled = compile_files(&[
+        ("main", "import timer\nasync fun go num:\n  x = await timer.wait 1\n  x\n"),
+        ("timer", "export async fun wait (n num) num: n\n"),
+    ]);
+
+    compiled.ok();
+
+    assert!(compiled.lua("timer").starts_with("local __async = require(\"__async\")"), "{}", compiled.lua("timer"));
+    assert!(compiled.lua("__async").contains("coroutine"), "{}", compiled.lua("__async"));
+    assert!(!compiled.lua("main").contains("coroutine.create"), "{}", compiled.lua("main"));
+}
+
+#[test]
+fn generators_yield_one_type() {
+    let lua = compile("fun names:\n  yield \"a\"\n  yield \"b\"\nfor x in names!:\n  y = x ++ \"!\"\n").ok();
+
+    assert!(lua.contains("return coroutine.wrap(function()\ncoroutine.yield(\"a\")\ncoroutine.yield(\"b\")\nend)"), "{}", lua);
+    assert!(lua.contains("for x in names() do"), "{}", lua);
+
+    let compiled = compile("fun mixed:\n  yield 1\n  yield \"a\"\n");
+
+    assert!(compiled.output.contains("yield: expected 'Num', got 'Str'"), "{}", compiled.output);
+
+    // however deep the yields are
+    let compiled = compile("fun mixed:\n  if true:\n    yield 1\n  else:\n    yield \"a\"\n");
+
+    assert!(compiled.output.contains("yield: expected 'Num', got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile("yield 1\n");
+
+    assert!(compiled.output.contains("yield outside of a fun"), "{}", compiled.output);
+}
+
+#[test]
+fn generators_are_typed_as_iters() {
+    let compiled = compile("fun names:\n  yield \"a\"\nfor x in names!:\n  y = x / 2\n");
+
+    assert!(compiled.output.contains("failed to divide: Str and Num"), "{}", compiled.output);
+
+    let compiled = compile("fun names:\n  yield \"a\"\nn num = names!\n");
+
+    assert!(compiled.output.contains("n: expected 'Num', got 'Iter(Str)'"), "{}", compiled.output);
+}
+
+#[test]
+fn parenthesized_keys_are_taken_whole() {
+    let lua = compile("xs = [1, 2]\ni = 0\ny = xs.(i + 1)\nz = xs.(i + 1) * 2\n").ok();
+
+    assert!(lua.contains("local y = xs[i + 1]"), "{}", lua);
+    assert!(lua.contains("local z = xs[i + 1] * 2"), "{}", lua);
+}
+
+#[test]
+fn compound_targets_are_evaluated_once() {
+    let lua = compile("xs = [1, 2]\nfun k num: 1\nxs.(k!) += 1\n\nrecord P: hp num\np = P 1\nfun get P: p\n(get!).hp += 1\n").ok();
+
+    // the definitions and one call each
+    assert_eq!(lua.matches("k()").count(), 2, "{}", lua);
+    assert_eq!(lua.matches("get()").count(), 2, "{}", lua);
+    assert!(lua.contains("__object"), "{}", lua);
+}
+
+#[test]
+fn destructuring_is_keyed_by_syntax() {
+    let lua = compile("p any = 1\n[a, b] = p\n[hp: hp] = p\n").ok();
+
+    assert!(lua.contains("local a, b = p[1], p[2]"), "{}", lua);
+    assert!(lua.contains("local hp = p.hp"), "{}", lua);
+}
+
+#[test]
+fn destructured_names_get_field_types() {
+    assert!(compile("[hp: hp, name: name] = [hp = 10, name = \"orc\"]\nz bool = hp\n").output.contains("expected 'Bool', got 'Num'"));
+
+    let source = "record Player: name str, hp num\nplayer = Player \"orc\", 10\n[hp: h, name: n] = player\n";
+    compile(source).ok();
+
+    assert!(compile(&format!("{}w bool = n\n", source)).output.contains("expected 'Bool', got 'Str'"));
+}
+
+#[test]
+fn named_args_stop_at_headers() {
+    assert!(compile("fun w (a any) any: a\nt = true\nw ok t:\n  t = false\n").output.contains("error"));
+
+    let lua = compile("fun ok (a any) bool: true\nt = 1\nif ok t:\n  t = 2\nmatch ok t:\n  true: 1\n  _: 2\n").ok();
+
+    assert!(lua.contains("if ok(t) then"), "{}", lua);
+    assert!(lua.contains("local __match0 = ok(t)"), "{}", lua);
+}
+
+#[test]
+fn named_args_in_parens() {
+    let lua = compile("fun spawn (x num = 1, y num = 2) num: x + y\na = spawn(x = 10)\nb = spawn(y = 2, x = 1)\nc = spawn(y: 5)\nd = spawn (3) + 1\n").ok();
+
+    assert!(lua.contains("local a = spawn(10)"), "{}", lua);
+    assert!(lua.contains("local b = spawn(1,2)"), "{}", lua);
+    assert!(lua.contains("local c = spawn(nil,5)"), "{}", lua);
+    assert!(lua.contains("local d = spawn(3 + 1)"), "{}", lua);
+}
+
+#[test]
+fn fun_blocks_after_definitions() {
+    // indented as in the readme
+    let lua = compile("a =\n \u{a0} fun (b num) num:\n \u{a0}  \u{a0} b + 10\n").ok();
+
+    assert_eq!(lua.matches("b + 10").count(), 1, "{}", lua);
+    assert!(lua.contains("local a = function(b)"), "{}", lua);
+}
+
+#[test]
+fn variants_as_call_args() {
+    let lua = compile("enum Shape: Circle(r num), Empty\nfun area (s Shape) num: 1\narea Shape.Circle 2\nw = area Shape.Empty\n").ok();
+
+    assert!(lua.contains("area(Shape.Circle(2))"), "{}", lua);
+    assert!(lua.contains("local w = area(Shape.Empty)"), "{}", lua);
+}
+
+#[test]
+fn unknown_types_are_reported() {
+    assert!(compile("fun f (p Playr): p\n").output.contains("unknown type: Playr"));
+    assert!(compile("x Vec3 = 1\n").output.contains("unknown type: Vec3"));
+
+    compile("fun spawn (x num = 1, y num = 2) num: x\nspawn y = 2, x = 1\n").ok();
+}
+
+#[test]
+fn narrowed_names_keep_their_declared_type() {
+    compile("hp num? = nil\nif hp != nil:\n  hp = nil\n").ok();
+
+    assert!(compile("hp num? = nil\nif hp != nil:\n  hp = \"a\"\n").output.contains("hp: can't mutate type"));
+    assert!(compile("hp num? = nil\nif hp != nil:\n  hp = nil\n  x = hp + 1\n").output.contains("error"));
+}
+
+#[test]
+fn modules_keep_their_funs_and_types_local() {
+    let compiled = compile_files(&[
+        ("main", "import button\nok = button.make \"ok\"\n"),
+        ("button", "export record Btn: label str\nexport fun make (label str) Btn: Btn label\nfun helper: 2\n"),
+    ]);
+
+    compiled.ok();
+
+    let lua = compiled.lua("button");
+
+    assert!(lua.starts_with("local Btn, make, helper\n"), "{}", lua);
+    assert!(lua.ends_with("return {Btn = Btn, make = make}\n"), "{}", lua);
+}
+
+#[test]
+fn exported_types_as_annotations() {
+    let button = ("button", "export record Btn: label str\nexport fun make (label str) Btn: Btn label\n");
+
+    compile_files(&[("main", "import button\nfun f (x button.Btn) str: x.label\nb button.Btn = button.make \"ok\"\ns = f b\n"), button]).ok();
+
+    assert!(compile_files(&[("main", "import button\nb button.Btn = 1\n"), button]).output.contains("expected"));
+}
+
+#[test]
+fn errors_fail_the_compiler() {
+    assert!(compile("x num = 1\n").success);
+
+    for compiled in [
+        compile("x num = \"a\"\n"),
+        compile("x = )\n"),
+        compile_files(&[("main", "import a\n"), ("a", "import main\n")]),
+    ].iter() {
+        assert!(compiled.output.contains("error"), "{}", compiled.output);
+        assert!(!compiled.success, "{}", compiled.output);
+    }
+}
+
+#[test]
+fn failed_statements_are_reported_once() {
+    let compiled = compile("x = match 1:\n  1: )\n  _: 2\ny = x + 1\n");
+
+    assert_eq!(compiled.output.matches("unexpected").count(), 1, "{}", compiled.output);
+
+    let compiled = compile("a, b num = )\nc = a + b\nfun f (n num) num: )\nd = f 1\n");
+
+    assert_eq!(compiled.output.matches("unexpected").count(), 2, "{}", compiled.output);
+}
+
+#[test]
+fn comparisons_need_their_own_operator() {
+    let source = "record V:\n  x num\n  op < (o V) bool: self.x < o.x\na = V 1\nb = V 2\nc = a < b\nd = a > b\n";
+
+    compile(source).ok();
+
+    assert!(compile(&format!("{}e = a <= b\n", source)).output.contains("needs 'op <='"));
+    assert!(compile(&format!("{}e = a >= b\n", source)).output.contains("needs 'op <='"));
+    assert!(compile("record W: x num\na = W 1\nb = a < a\n").output.contains("needs 'op <'"));
+}
+
+#[test]
+fn typed_catches_test_the_error() {
+    let lua = compile("record BadInput: msg str, code num?\nfun f num:\n  try:\n    return 1\n  catch err BadInput:\n    return 0\n").ok();
+
+    assert!(lua.contains("if not __try0[1] and type(__try0[2]) == \"table\" and __try0[2].msg ~= nil then"), "{}", lua);
+    // the rest are thrown on
+    assert!(lua.contains("if not __try0[1] then error(__try0[2], 0) end"), "{}", lua);
+
+    let lua = compile("fun f num:\n  try:\n    return 1\n  catch err str:\n    return 0\n").ok();
+
+    assert!(lua.contains("if not __try0[1] and type(__try0[2]) == \"string\" then"), "{}", lua);
+
+    assert!(compile("fun f num:\n  try:\n    return 1\n  catch err num..:\n    return 0\n").output.contains("can't tell errors"));
+}
+
+#[test]
+fn outer_names_are_assigned_not_shadowed() {
+    let lua = compile("x = 0\nc = true\nif c:\n  x = 1\n  z = 2\ny = x\nfor i in [1, 2]:\n  x = i\n").ok();
+
+    assert!(lua.contains("local x = 0"), "{}", lua);
+    assert!(lua.contains("if c then\nx = 1\nlocal z = 2\nend"), "{}", lua);
+    assert!(lua.contains("local y = x"), "{}", lua);
+    assert!(lua.contains("do\nx = i\nend"), "{}", lua);
+    assert_eq!(lua.matches("local x").count(), 1, "{}", lua);
+
+    let lua = compile("x = 0\nmatch x:\n  0:\n    x = 1\n  _:\n    x = 2\n").ok();
+
+    assert!(lua.contains("then\nx = 1\nelse\nx = 2\nend"), "{}", lua);
+}
+
+#[test]
+fn matches_warn_and_stop_at_catch_alls() {
+    let compiled = compile("x = 1\ny = match x:\n  1: \"one\"\n  _: \"other\"\n  2: \"two\"\n");
+    let lua      = compiled.ok();
+
+    assert!(compiled.output.contains("unreachable match arm: 2"), "{}", compiled.output);
+    assert!(!lua.contains("\"two\""), "{}", lua);
+    assert!(lua.contains("else\nreturn \"other\"\nend"), "{}", lua);
+
+    let compiled = compile("enum Dir: Up, Down\nd = Dir.Up\nmatch d:\n  Dir.Up: 1\n");
+
+    assert!(compiled.output.contains("non-exhaustive match on d: missing Down"), "{}", compiled.output);
+
+    let compiled = compile("enum Dir: Up, Down\nd = Dir.Up\nmatch d:\n  Dir.Up: 1\n  Dir.Down: 2\n  _: 3\n");
+
+    assert!(compiled.output.contains("unreachable match arm: _"), "{}", compiled.output);
+    assert!(!compiled.output.contains("non-exhaustive"), "{}", compiled.output);
+}
+
+#[test]
+fn unclosed_brackets_end_with_their_block() {
+    let compiled = compile("d = [1, 2\nx = 1\ny bool = x\n");
+
+    assert_eq!(compiled.output.matches("unclosed '['").count(), 1, "{}", compiled.output);
+    assert!(compiled.output.contains("y: expected 'Bool', got 'Num'"), "{}", compiled.output);
+
+    let compiled = compile("fun f:\n  d = [1, 2\nfun g:\n  x bool = 1\n");
+
+    assert_eq!(compiled.output.matches("unclosed '['").count(), 1, "{}", compiled.output);
+    assert!(compiled.output.contains("x: expected 'Bool', got 'Num'"), "{}", compiled.output);
+
+    let lua = compile("fun f:\n  e = [\n    1,\n  ]\n  e\n").ok();
+
+    assert!(lua.contains("local e = {1,}"), "{}", lua);
+}
+
+#[test]
+fn multiple_assignment_takes_any_values_and_swaps() {
+    let lua = compile("fun two: return 1, 2\np, q = two!\nr any = 1\ns, t = r\n").ok();
+
+    assert!(lua.contains("local p, q = two()"), "{}", lua);
+    assert!(lua.contains("local s, t = r"), "{}", lua);
+
+    let lua = compile("fun f:\n  a = 1\n  b = 2\n  a, b = b, a\n").ok();
+
+    assert!(lua.contains("\na, b = b, a\n"), "{}", lua);
+}
+
+#[test]
+fn destructured_names_are_fields_of_keyed_values() {
+    let source = "record Player: name str, hp num\nplayer = Player \"orc\", 10\n[hp, name] = player\n";
+    let lua    = compile(source).ok();
+
+    assert!(lua.contains("local hp, name = player.hp, player.name"), "{}", lua);
+
+    let lua = compile("[hp, name] = [hp = 10, name = \"orc\"]\n").ok();
+
+    assert!(lua.contains("local hp, name = __value0.hp, __value0.name"), "{}", lua);
+
+    assert!(compile(&format!("{}w bool = name\n", source)).output.contains("expected 'Bool', got 'Str'"));
+    assert!(compile("record P: hp num\np = P 1\n[mana] = p\n").output.contains("no field 'mana'"));
+}
+
+#[test]
+fn class_methods_satisfy_interfaces() {
+    let source = "record Rect: w num, h num\ninterface Drawable: draw fun(), bounds fun() Rect\nfun render (d Drawable) Rect:\n  d.bounds!\nclass Sprite:\n  w num\n  fun draw:\n    1\n  fun bounds Rect:\n    Rect self.w, self.w\n";
+
+    compile(&format!("{}r = render Sprite.new 2\n", source)).ok();
+
+    let compiled = compile(&format!("{}class Blob:\n  w num\n  fun draw:\n    1\nr = render Blob.new 1\n", source));
+
+    assert!(compiled.output.contains("render: expected 'Interface(\"Drawable\""), "{}", compiled.output);
+}
+
+#[test]
+fn keyed_tables_satisfy_interfaces() {
+    let source = "interface Drawable: draw fun(), bounds fun() num\nfun render (d Drawable) num:\n  d.bounds!\n";
+
+    let lua = compile(&format!("{}t = [draw = fun: 1, bounds = fun: 2]\nr = render t\n", source)).ok();
+
+    assert!(lua.contains("local r = render(t)"), "{}", lua);
+    assert!(!lua.contains("Drawable"), "{}", lua);
+
+    let compiled = compile(&format!("{}t = [draw = 1, bounds = 2]\nr = render t\n", source));
+
+    assert!(compiled.output.contains("render: expected 'Interface(\"Drawable\""), "{}", compiled.output);
+}
+
+#[test]
+fn named_args_run_in_source_order() {
+    let lua = compile("fun f (a any, b any) any: a\nfun g any: 1\nfun h any: 2\nx = f b = g!, a = h!\ny = f a = h!, b = g!\nz = f b = 1, a = h!\n").ok();
+
+    assert!(lua.contains("local x = (function(__arg0,__arg1) return f(__arg1,__arg0) end)(g(),h())"), "{}", lua);
+    assert!(lua.contains("local y = f(h(),g())"), "{}", lua);
+    assert!(lua.contains("local z = f(h(),1)"), "{}", lua);
+}
+
+#[test]
+fn user_index_is_not_pure() {
+    let lua = compile("record Grid:\n  w num\n  op [] (k num) num: k * 2\ng = Grid 1\nfun f (a num, b num) num: a + b\nfun h num: 1\nx = g.(3) |> f(h!)\ny = g.w |> f(h!)\nz = f b = h!, a = g.(3)\n").ok();
+
+    assert!(lua.contains("local x = (function(__pipe1) return f(__pipe1,h()) end)(g[3])"), "{}", lua);
+    assert!(lua.contains("local y = f(g.w,h())"), "{}", lua);
+    assert!(lua.contains("local z = (function(__arg2,__arg3) return f(__arg3,__arg2) end)(h(),g[3])"), "{}", lua);
+}
+
+#[test]
+fn pipe_targets_take_juxtaposed_args() {
+    let lua = compile("fun f (a num, b num) num: a + b\nfun g (a num) num: a\nx = 1 |> f 2\ny = 1 |> g |> f 3\nz = [1 |> g, 2]\n").ok();
+
+    assert!(lua.contains("local x = f(1,2)\n"), "{}", lua);
+    assert!(lua.contains("local y = f(g(1),3)\n"), "{}", lua);
+    assert!(lua.contains("local z = {g(1),2,}"), "{}", lua);
+
+    let compiled = compile("fun g (a num) num: a\nx = 1 |> g + 2\n");
+
+    assert!(compiled.output.contains("unexpected '+' after pipe target"), "{}", compiled.output);
+    assert!(compiled.lua("main").is_empty(), "{}", compiled.lua("main"));
+}
+
+#[test]
+fn match_values_do_not_return() {
+    let compiled = compile("fun outer (y num) num:\n  x = match y:\n    1: return 5\n    _: 2\n  x + 1\n");
+
+    assert!(compiled.output.contains("1: can't return from a match used as a value"), "{}", compiled.output);
+
+    let compiled = compile("fun outer (y num) num:\n  x = match y:\n    1:\n      if y > 0: return 5\n      3\n    _: 2\n  x + 1\n");
+
+    assert!(compiled.output.contains("can't return from a match used as a value"), "{}", compiled.output);
+
+    // as a statement the return leaves the fun
+    let lua = compile("fun outer (y num) num:\n  match y:\n    1: return 5\n    _: 2\n").ok();
+
+    assert!(lua.contains("if __match0 == 1 then\nreturn 5\n"), "{}", lua);
+}
+
+#[test]
+fn generated_constructors_call_the_parents() {
+    let source = "class A:\n  x num\n  fun new (a num, b num):\n    self.x = a + b\nclass B extends A:\n  y num\n";
+
+    let lua = compile(&format!("{}class C extends B:\n  z str\nc = C.new 1, 2, 3, \"z\"\n", source)).ok();
+
+    assert!(lua.contains("function B.init(self,a,b,y)\nA.init(self,a,b)\nself.y = y\nend"), "{}", lua);
+    assert!(lua.contains("function C.init(self,a,b,y,z)\nB.init(self,a,b,y)\nself.z = z\nend"), "{}", lua);
+
+    let compiled = compile(&format!("{}b = B.new 1, 2\n", source));
+
+    assert!(compiled.output.contains("B.new: expected at least 3 args, got 2"), "{}", compiled.output);
+
+    let compiled = compile("class A:\n  x num\n  fun new (y num):\n    self.x = y\nclass B extends A:\n  y num\n");
+
+    assert!(compiled.output.contains("B: needs a new, A's also takes 'y'"), "{}", compiled.output);
+}
+
+#[test]
+fn diagnostics_on_different_lines_are_all_reported() {
+    let compiled = compile("x = )\ny = 1\nz = )\n");
+
+    assert!(compiled.output.contains("(line 1 col 4): unexpected symbol: )"), "{}", compiled.output);
+    assert!(compiled.output.contains("(line 3 col 4): unexpected symbol: )"), "{}", compiled.output);
+
+    let compiled = compile("fun f:\n  x = )\nfun g:\n  x = )\n");
+
+    assert_eq!(compiled.output.matches("unexpected symbol").count(), 2, "{}", compiled.output);
+    assert!(compiled.output.contains("(line 4 col 6)"), "{}", compiled.output);
+}
+
+#[test]
+fn keyed_literals_are_any_tables() {
+    let lua = compile("b any .. = [\n  a = fun (b num) num:\n    b + 10\n]\n").ok();
+
+    assert!(lua.contains("local b = {a = function(b)"), "{}", lua);
+
+    let compiled = compile("b num .. = [a = 1]\n");
+
+    assert!(compiled.output.contains("b: expected 'Array(Num)', got 'Map(Str, Num)'"), "{}", compiled.output);
+}
+
+#[test]
+fn maps_check_keys_and_values() {
+    let lua = compile("m {num: str} = []\nm.(1) = \"a\"\nx str? = m.(1)\nfor k, v in m:\n  n num = k\n  s str = v\n").ok();
+
+    assert!(lua.contains("m[1] = \"a\"\nlocal x = m[1]\nfor k, v in pairs(m) do"), "{}", lua);
+
+    let compiled = compile("m {num: str} = []\nm.(\"x\") = \"b\"\n");
+
+    assert!(compiled.output.contains("m: expected key 'Num', got 'Str'"), "{}", compiled.output);
+
+    let compiled = compile("m = [a = 1, b = 2]\nm.d = \"x\"\n");
+
+    assert!(compiled.output.contains("m.d: expected 'Optional(Num)', got 'Str'"), "{}", compiled.output);
+
+    // reads might miss
+    let compiled = compile("m = [a = 1]\nx num = m.a\n");
+
+    assert!(compiled.output.contains("x: expected 'Num', got 'Optional(Num)'"), "{}", compiled.output);
+
+    let compiled = compile("m = [a = 1]\nfor k, v in m:\n  t str = v\n");
+
+    assert!(compiled.output.contains("t: expected 'Str', got 'Num'"), "{}", compiled.output);
+}
+
+#[test]
+fn operator_types_name_their_record() {
+    let ops = ["+", "-", "*", "/", "%", "^", "++"];
+    let source: String = ops.iter().map(|op| format!("  op {} (o V) V: o\n", op)).collect();
+
+    let compiled = compile(&format!("record V:\n  x num\n{}q str = V 1\n", source));
+
+    assert!(compiled.output.contains("q: expected 'Str'"), "{}", compiled.output);
+    assert!(compiled.output.len() < 2000, "{}", compiled.output.len());
+
+    // the metatable doesn't take a name of the program's
+    let lua = compile("V_ops = 5\nrecord V:\n  x num\n  op + (o V) V: o\na = V 1\nb = V_ops + 1\n").ok();
+
+    assert!(lua.contains("local V_ops = 5\n"), "{}", lua);
+    assert!(lua.matches("V_ops").count() == 2, "{}", lua);
+}